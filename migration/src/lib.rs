@@ -12,6 +12,25 @@ mod m20221014_125218_create_indices;
 mod m20221021_115605_add_indices_to_foreign_keys;
 mod m20221024_135934_create_swap_table;
 mod m20221026_160617_drop_timestamp_column;
+mod m20221103_101512_add_valid_to_transaction;
+mod m20221108_143207_add_timestamp_to_block;
+mod m20221115_091342_create_candle_table;
+mod m20221121_104455_create_mean_exchange_ratio_table;
+mod m20221128_153021_use_decimal_for_prices;
+mod m20221202_110734_add_metadata_to_token;
+mod m20221207_142950_add_logo_to_token;
+mod m20221212_093518_normalize_pair_order;
+mod m20221216_161204_add_trades_to_candle;
+mod m20221219_103846_add_credentials_to_swap;
+mod m20221221_152417_add_output_references;
+mod m20221228_094530_add_order_parameters_to_swap;
+mod m20230104_141209_create_order_table;
+mod m20230111_103412_create_batch_table;
+mod m20230118_154820_create_mev_alert_table;
+mod m20230125_132047_create_arbitrage_opportunity_table;
+mod m20230130_101523_flip_wingriders_swap_direction;
+mod m20230201_091544_add_spent_tx_id_to_transaction_output;
+mod m20230203_102611_add_position_to_swap;
 
 pub struct Migrator;
 
@@ -31,6 +50,25 @@ impl MigratorTrait for Migrator {
             Box::new(m20221021_115605_add_indices_to_foreign_keys::Migration),
             Box::new(m20221024_135934_create_swap_table::Migration),
             Box::new(m20221026_160617_drop_timestamp_column::Migration),
+            Box::new(m20221103_101512_add_valid_to_transaction::Migration),
+            Box::new(m20221108_143207_add_timestamp_to_block::Migration),
+            Box::new(m20221115_091342_create_candle_table::Migration),
+            Box::new(m20221121_104455_create_mean_exchange_ratio_table::Migration),
+            Box::new(m20221128_153021_use_decimal_for_prices::Migration),
+            Box::new(m20221202_110734_add_metadata_to_token::Migration),
+            Box::new(m20221207_142950_add_logo_to_token::Migration),
+            Box::new(m20221212_093518_normalize_pair_order::Migration),
+            Box::new(m20221216_161204_add_trades_to_candle::Migration),
+            Box::new(m20221219_103846_add_credentials_to_swap::Migration),
+            Box::new(m20221221_152417_add_output_references::Migration),
+            Box::new(m20221228_094530_add_order_parameters_to_swap::Migration),
+            Box::new(m20230104_141209_create_order_table::Migration),
+            Box::new(m20230111_103412_create_batch_table::Migration),
+            Box::new(m20230118_154820_create_mev_alert_table::Migration),
+            Box::new(m20230125_132047_create_arbitrage_opportunity_table::Migration),
+            Box::new(m20230130_101523_flip_wingriders_swap_direction::Migration),
+            Box::new(m20230201_091544_add_spent_tx_id_to_transaction_output::Migration),
+            Box::new(m20230203_102611_add_position_to_swap::Migration),
         ]
     }
 }
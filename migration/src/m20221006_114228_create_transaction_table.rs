@@ -1,4 +1,6 @@
-use sea_orm_migration::prelude::*;
+use sea_orm_migration::{prelude::*, sea_orm::DbBackend};
+
+use crate::m20221010_123828_create_block_table::Block;
 
 #[derive(DeriveMigrationName)]
 pub struct Migration;
@@ -6,6 +8,44 @@ pub struct Migration;
 #[async_trait::async_trait]
 impl MigrationTrait for Migration {
     async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // SQLite can't add a foreign key (nor a NOT NULL column) to an existing table, so there
+        // the block_id column is created with the table instead of by the block table migration.
+        if manager.get_database_backend() == DbBackend::Sqlite {
+            return manager
+                .create_table(
+                    Table::create()
+                        .table(Transaction::Table)
+                        .if_not_exists()
+                        .col(
+                            ColumnDef::new(Transaction::Id)
+                                .big_integer()
+                                .not_null()
+                                .auto_increment()
+                                .primary_key(),
+                        )
+                        .col(
+                            ColumnDef::new(Transaction::Hash)
+                                .binary()
+                                .not_null()
+                                .unique_key(),
+                        )
+                        .col(
+                            ColumnDef::new(Transaction::BlockId)
+                                .big_integer()
+                                .not_null(),
+                        )
+                        .foreign_key(
+                            ForeignKey::create()
+                                .name("fk-transaction-block_id")
+                                .from(Transaction::Table, Transaction::BlockId)
+                                .to(Block::Table, Block::Id)
+                                .on_delete(ForeignKeyAction::Cascade),
+                        )
+                        .to_owned(),
+                )
+                .await;
+        }
+
         manager
             .create_table(
                 Table::create()
@@ -43,4 +83,5 @@ pub enum Transaction {
     Id,
     Hash,
     BlockId,
+    Valid,
 }
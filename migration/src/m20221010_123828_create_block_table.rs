@@ -1,4 +1,4 @@
-use sea_orm_migration::prelude::*;
+use sea_orm_migration::{prelude::*, sea_orm::DbBackend};
 
 use crate::m20221006_114228_create_transaction_table::Transaction;
 
@@ -36,6 +36,11 @@ impl MigrationTrait for Migration {
             )
             .await?;
 
+        // The column is already created by the transaction table migration on SQLite
+        if manager.get_database_backend() == DbBackend::Sqlite {
+            return Ok(());
+        }
+
         manager
             .alter_table(
                 Table::alter()
@@ -59,6 +64,12 @@ impl MigrationTrait for Migration {
     }
 
     async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        if manager.get_database_backend() == DbBackend::Sqlite {
+            return manager
+                .drop_table(Table::drop().table(Block::Table).to_owned())
+                .await;
+        }
+
         manager
             .alter_table(
                 Table::alter()
@@ -84,4 +95,5 @@ pub enum Block {
     Epoch,
     Slot,
     PreviousBlockId,
+    Timestamp,
 }
@@ -96,4 +96,5 @@ pub enum PriceUpdate {
     Amount1,
     Amount2,
     Timestamp,
+    OutputIndex,
 }
@@ -1,4 +1,7 @@
-use sea_orm_migration::prelude::*;
+use sea_orm_migration::{
+    prelude::*,
+    sea_orm::{ConnectionTrait, DbBackend, Statement},
+};
 
 #[derive(DeriveMigrationName)]
 pub struct Migration;
@@ -6,6 +9,31 @@ pub struct Migration;
 #[async_trait::async_trait]
 impl MigrationTrait for Migration {
     async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // SQLite supports only a single change per ALTER TABLE statement, and the query builder
+        // can't drop columns there
+        if manager.get_database_backend() == DbBackend::Sqlite {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(TransactionOutput::Table)
+                        .add_column(
+                            ColumnDef::new(TransactionOutput::DatumHash)
+                                .string_len(64)
+                                .null(),
+                        )
+                        .to_owned(),
+                )
+                .await?;
+            return manager
+                .get_connection()
+                .execute(Statement::from_string(
+                    DbBackend::Sqlite,
+                    "ALTER TABLE transaction_output DROP COLUMN spent".to_owned(),
+                ))
+                .await
+                .map(|_| ());
+        }
+
         manager
             .alter_table(
                 Table::alter()
@@ -22,6 +50,29 @@ impl MigrationTrait for Migration {
     }
 
     async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        if manager.get_database_backend() == DbBackend::Sqlite {
+            manager
+                .get_connection()
+                .execute(Statement::from_string(
+                    DbBackend::Sqlite,
+                    "ALTER TABLE transaction_output DROP COLUMN datum_hash".to_owned(),
+                ))
+                .await?;
+            return manager
+                .alter_table(
+                    Table::alter()
+                        .table(TransactionOutput::Table)
+                        .add_column(
+                            ColumnDef::new(TransactionOutput::Spent)
+                                .boolean()
+                                .not_null()
+                                .default(Value::Bool(Some(false))),
+                        )
+                        .to_owned(),
+                )
+                .await;
+        }
+
         manager
             .alter_table(
                 Table::alter()
@@ -75,4 +75,11 @@ pub enum Swap {
     Amount1,
     Amount2,
     Direction,
+    PaymentCredential,
+    StakeCredential,
+    OrderTxHash,
+    OrderIndex,
+    MinReceive,
+    Deadline,
+    Slippage,
 }
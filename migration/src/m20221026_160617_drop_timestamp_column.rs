@@ -1,4 +1,7 @@
-use sea_orm_migration::prelude::*;
+use sea_orm_migration::{
+    prelude::*,
+    sea_orm::{ConnectionTrait, DbBackend, Statement},
+};
 
 use crate::m20221013_162928_create_price_update_table::PriceUpdate;
 
@@ -8,6 +11,18 @@ pub struct Migration;
 #[async_trait::async_trait]
 impl MigrationTrait for Migration {
     async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // The query builder can't drop columns on SQLite
+        if manager.get_database_backend() == DbBackend::Sqlite {
+            return manager
+                .get_connection()
+                .execute(Statement::from_string(
+                    DbBackend::Sqlite,
+                    "ALTER TABLE price_update DROP COLUMN timestamp".to_owned(),
+                ))
+                .await
+                .map(|_| ());
+        }
+
         manager
             .alter_table(
                 Table::alter()
@@ -0,0 +1,36 @@
+use sea_orm_migration::prelude::*;
+
+use crate::m20221006_114228_create_transaction_table::Transaction;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Transaction::Table)
+                    .add_column(
+                        ColumnDef::new(Transaction::Valid)
+                            .boolean()
+                            .not_null()
+                            .default(Value::Bool(Some(true))),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Transaction::Table)
+                    .drop_column(Transaction::Valid)
+                    .to_owned(),
+            )
+            .await
+    }
+}
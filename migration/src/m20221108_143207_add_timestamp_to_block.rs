@@ -0,0 +1,32 @@
+use sea_orm_migration::prelude::*;
+
+use crate::m20221010_123828_create_block_table::Block;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Nullable, as blocks stored before this migration don't have the timestamp computed
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Block::Table)
+                    .add_column(ColumnDef::new(Block::Timestamp).timestamp().null())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Block::Table)
+                    .drop_column(Block::Timestamp)
+                    .to_owned(),
+            )
+            .await
+    }
+}
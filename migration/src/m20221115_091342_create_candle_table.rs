@@ -0,0 +1,93 @@
+use sea_orm_migration::prelude::*;
+
+use crate::m20221006_141624_create_token_table::Token;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Candle::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Candle::Id)
+                            .big_integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    // Empty script hash stands for the aggregate over all pools of the pair
+                    .col(ColumnDef::new(Candle::ScriptHash).binary().not_null())
+                    .col(ColumnDef::new(Candle::Token1Id).big_integer().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-candle-token1_id")
+                            .from(Candle::Table, Candle::Token1Id)
+                            .to(Token::Table, Token::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .col(ColumnDef::new(Candle::Token2Id).big_integer().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-candle-token2_id")
+                            .from(Candle::Table, Candle::Token2Id)
+                            .to(Token::Table, Token::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .col(ColumnDef::new(Candle::Interval).string_len(2).not_null())
+                    .col(ColumnDef::new(Candle::Start).timestamp().not_null())
+                    .col(ColumnDef::new(Candle::Open).double().not_null())
+                    .col(ColumnDef::new(Candle::High).double().not_null())
+                    .col(ColumnDef::new(Candle::Low).double().not_null())
+                    .col(ColumnDef::new(Candle::Close).double().not_null())
+                    .col(ColumnDef::new(Candle::Volume1).big_unsigned().not_null())
+                    .col(ColumnDef::new(Candle::Volume2).big_unsigned().not_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .table(Candle::Table)
+                    .name("index-candle-script_hash-token1_id-token2_id-interval-start")
+                    .col(Candle::ScriptHash)
+                    .col(Candle::Token1Id)
+                    .col(Candle::Token2Id)
+                    .col(Candle::Interval)
+                    .col(Candle::Start)
+                    .unique()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Candle::Table).to_owned())
+            .await
+    }
+}
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+pub enum Candle {
+    Table,
+    Id,
+    ScriptHash,
+    Token1Id,
+    Token2Id,
+    Interval,
+    Start,
+    Open,
+    High,
+    Low,
+    Close,
+    Volume1,
+    Volume2,
+    Trades,
+}
@@ -0,0 +1,87 @@
+use sea_orm_migration::prelude::*;
+
+use crate::m20221006_141624_create_token_table::Token;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(MeanExchangeRatio::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(MeanExchangeRatio::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(MeanExchangeRatio::Token1)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-mean_exchange_ratio-token1")
+                            .from(MeanExchangeRatio::Table, MeanExchangeRatio::Token1)
+                            .to(Token::Table, Token::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .col(
+                        ColumnDef::new(MeanExchangeRatio::Token2)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-mean_exchange_ratio-token2")
+                            .from(MeanExchangeRatio::Table, MeanExchangeRatio::Token2)
+                            .to(Token::Table, Token::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .col(ColumnDef::new(MeanExchangeRatio::Ratio).float().not_null())
+                    .col(
+                        ColumnDef::new(MeanExchangeRatio::Date)
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .table(MeanExchangeRatio::Table)
+                    .name("index-mean_exchange_ratio-token1-token2-date")
+                    .col(MeanExchangeRatio::Token1)
+                    .col(MeanExchangeRatio::Token2)
+                    .col(MeanExchangeRatio::Date)
+                    .unique()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(MeanExchangeRatio::Table).to_owned())
+            .await
+    }
+}
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+pub enum MeanExchangeRatio {
+    Table,
+    Id,
+    Token1,
+    Token2,
+    Ratio,
+    Date,
+}
@@ -0,0 +1,69 @@
+use sea_orm_migration::{prelude::*, sea_orm::DbBackend};
+
+use crate::m20221115_091342_create_candle_table::Candle;
+use crate::m20221121_104455_create_mean_exchange_ratio_table::MeanExchangeRatio;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // SQLite can't alter column types and stores numerics with type affinity anyway
+        if manager.get_database_backend() == DbBackend::Sqlite {
+            return Ok(());
+        }
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Candle::Table)
+                    .modify_column(ColumnDef::new(Candle::Open).decimal().not_null())
+                    .modify_column(ColumnDef::new(Candle::High).decimal().not_null())
+                    .modify_column(ColumnDef::new(Candle::Low).decimal().not_null())
+                    .modify_column(ColumnDef::new(Candle::Close).decimal().not_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(MeanExchangeRatio::Table)
+                    .modify_column(
+                        ColumnDef::new(MeanExchangeRatio::Ratio)
+                            .decimal()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        if manager.get_database_backend() == DbBackend::Sqlite {
+            return Ok(());
+        }
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Candle::Table)
+                    .modify_column(ColumnDef::new(Candle::Open).double().not_null())
+                    .modify_column(ColumnDef::new(Candle::High).double().not_null())
+                    .modify_column(ColumnDef::new(Candle::Low).double().not_null())
+                    .modify_column(ColumnDef::new(Candle::Close).double().not_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(MeanExchangeRatio::Table)
+                    .modify_column(ColumnDef::new(MeanExchangeRatio::Ratio).float().not_null())
+                    .to_owned(),
+            )
+            .await
+    }
+}
@@ -0,0 +1,58 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // SQLite supports only a single change per ALTER TABLE statement
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Token::Table)
+                    .add_column(ColumnDef::new(Token::Decimals).integer().null())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Token::Table)
+                    .add_column(ColumnDef::new(Token::Ticker).string().null())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Token::Table)
+                    .add_column(ColumnDef::new(Token::DisplayName).string().null())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for column in [Token::Decimals, Token::Ticker, Token::DisplayName] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Token::Table)
+                        .drop_column(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+pub enum Token {
+    Table,
+    Decimals,
+    Ticker,
+    DisplayName,
+}
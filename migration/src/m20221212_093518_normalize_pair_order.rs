@@ -0,0 +1,58 @@
+use sea_orm_migration::{
+    prelude::*,
+    sea_orm::{ConnectionTrait, Statement},
+};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+// Rows whose tokens are not in the canonical order, i.e. ordered by policy id and then by name.
+fn reversed_condition(table: &str, token1: &str, token2: &str) -> String {
+    format!(
+        r#"EXISTS (
+            SELECT 1 FROM token AS t1, token AS t2
+            WHERE t1.id = {table}.{token1} AND t2.id = {table}.{token2}
+            AND (t1.policy_id > t2.policy_id OR (t1.policy_id = t2.policy_id AND t1.name > t2.name))
+        )"#
+    )
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Plain SQL understood by both Postgres and SQLite, the assignments use the old values
+        let statements = [
+            format!(
+                "UPDATE price_update SET token1_id = token2_id, token2_id = token1_id, \
+                 amount1 = amount2, amount2 = amount1 WHERE {}",
+                reversed_condition("price_update", "token1_id", "token2_id")
+            ),
+            format!(
+                "UPDATE swap SET token1_id = token2_id, token2_id = token1_id, \
+                 amount1 = amount2, amount2 = amount1, direction = NOT direction WHERE {}",
+                reversed_condition("swap", "token1_id", "token2_id")
+            ),
+            // Aggregates can't be flipped in place, as the same pair may be stored in both orders,
+            // the reversed ones are dropped and have to be recomputed
+            format!(
+                "DELETE FROM candle WHERE {}",
+                reversed_condition("candle", "token1_id", "token2_id")
+            ),
+            format!(
+                "DELETE FROM mean_exchange_ratio WHERE {}",
+                reversed_condition("mean_exchange_ratio", "token1", "token2")
+            ),
+        ];
+        let db = manager.get_connection();
+        for sql in statements.into_iter() {
+            db.execute(Statement::from_string(manager.get_database_backend(), sql))
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn down(&self, _manager: &SchemaManager) -> Result<(), DbErr> {
+        // The original order is not known anymore, the normalized data stays valid
+        Ok(())
+    }
+}
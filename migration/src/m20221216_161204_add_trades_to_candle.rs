@@ -0,0 +1,37 @@
+use sea_orm_migration::prelude::*;
+
+use crate::m20221115_091342_create_candle_table::Candle;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Candles stored before this migration don't count their trades
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Candle::Table)
+                    .add_column(
+                        ColumnDef::new(Candle::Trades)
+                            .big_integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Candle::Table)
+                    .drop_column(Candle::Trades)
+                    .to_owned(),
+            )
+            .await
+    }
+}
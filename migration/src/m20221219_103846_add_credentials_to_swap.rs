@@ -0,0 +1,70 @@
+use sea_orm_migration::prelude::*;
+
+use crate::m20221024_135934_create_swap_table::Swap;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Swaps stored before this migration have no known trader
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Swap::Table)
+                    .add_column(ColumnDef::new(Swap::PaymentCredential).binary().null())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Swap::Table)
+                    .add_column(ColumnDef::new(Swap::StakeCredential).binary().null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .table(Swap::Table)
+                    .name("index-swap-payment_credential")
+                    .col(Swap::PaymentCredential)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .table(Swap::Table)
+                    .name("index-swap-stake_credential")
+                    .col(Swap::StakeCredential)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for name in [
+            "index-swap-payment_credential",
+            "index-swap-stake_credential",
+        ] {
+            manager
+                .drop_index(Index::drop().table(Swap::Table).name(name).to_owned())
+                .await?;
+        }
+        for column in [Swap::PaymentCredential, Swap::StakeCredential] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Swap::Table)
+                        .drop_column(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,64 @@
+use sea_orm_migration::prelude::*;
+
+use crate::m20221013_162928_create_price_update_table::PriceUpdate;
+use crate::m20221024_135934_create_swap_table::Swap;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Rows stored before this migration have no known output reference
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(PriceUpdate::Table)
+                    .add_column(
+                        ColumnDef::new(PriceUpdate::OutputIndex)
+                            .big_integer()
+                            .null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Swap::Table)
+                    .add_column(ColumnDef::new(Swap::OrderTxHash).binary().null())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Swap::Table)
+                    .add_column(ColumnDef::new(Swap::OrderIndex).big_integer().null())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(PriceUpdate::Table)
+                    .drop_column(PriceUpdate::OutputIndex)
+                    .to_owned(),
+            )
+            .await?;
+        for column in [Swap::OrderTxHash, Swap::OrderIndex] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Swap::Table)
+                        .drop_column(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+}
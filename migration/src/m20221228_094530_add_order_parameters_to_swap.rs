@@ -0,0 +1,51 @@
+use sea_orm_migration::prelude::*;
+
+use crate::m20221024_135934_create_swap_table::Swap;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Nullable, not every DEX has all the parameters and older swaps have none
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Swap::Table)
+                    .add_column(ColumnDef::new(Swap::MinReceive).big_integer().null())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Swap::Table)
+                    .add_column(ColumnDef::new(Swap::Deadline).timestamp().null())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Swap::Table)
+                    .add_column(ColumnDef::new(Swap::Slippage).decimal().null())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for column in [Swap::MinReceive, Swap::Deadline, Swap::Slippage] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Swap::Table)
+                        .drop_column(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,106 @@
+use sea_orm_migration::prelude::*;
+
+use crate::m20221006_114228_create_transaction_table::Transaction;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Order::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Order::Id)
+                            .big_integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Order::ScriptHash).binary().not_null())
+                    .col(ColumnDef::new(Order::TxId).big_integer().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-order-tx_id")
+                            .from(Order::Table, Order::TxId)
+                            .to(Transaction::Table, Transaction::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .col(ColumnDef::new(Order::OutputIndex).big_integer().not_null())
+                    .col(ColumnDef::new(Order::PaymentCredential).binary().null())
+                    .col(ColumnDef::new(Order::StakeCredential).binary().null())
+                    .col(ColumnDef::new(Order::MinReceive).big_integer().null())
+                    .col(ColumnDef::new(Order::Deadline).timestamp().null())
+                    .col(ColumnDef::new(Order::Datum).text().null())
+                    // The order is open until a transaction spends it, rolling the transaction
+                    // back opens the order again
+                    .col(ColumnDef::new(Order::SpentTxId).big_integer().null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-order-spent_tx_id")
+                            .from(Order::Table, Order::SpentTxId)
+                            .to(Transaction::Table, Transaction::Id)
+                            .on_delete(ForeignKeyAction::SetNull),
+                    )
+                    .col(ColumnDef::new(Order::Outcome).string().null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .table(Order::Table)
+                    .name("index-order-tx_id-output_index")
+                    .col(Order::TxId)
+                    .col(Order::OutputIndex)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .table(Order::Table)
+                    .name("index-order-script_hash-spent_tx_id")
+                    .col(Order::ScriptHash)
+                    .col(Order::SpentTxId)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .table(Order::Table)
+                    .name("index-order-spent_tx_id")
+                    .col(Order::SpentTxId)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Order::Table).to_owned())
+            .await
+    }
+}
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+pub enum Order {
+    Table,
+    Id,
+    ScriptHash,
+    TxId,
+    OutputIndex,
+    PaymentCredential,
+    StakeCredential,
+    MinReceive,
+    Deadline,
+    Datum,
+    SpentTxId,
+    Outcome,
+}
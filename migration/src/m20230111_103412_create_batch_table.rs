@@ -0,0 +1,88 @@
+use sea_orm_migration::prelude::*;
+
+use crate::m20221006_114228_create_transaction_table::Transaction;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Batch::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Batch::Id)
+                            .big_integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Batch::TxId).big_integer().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-batch-tx_id")
+                            .from(Batch::Table, Batch::TxId)
+                            .to(Transaction::Table, Transaction::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .col(ColumnDef::new(Batch::ScriptHash).binary().not_null())
+                    // Key hash of the batcher signing the transaction
+                    .col(ColumnDef::new(Batch::Batcher).binary().null())
+                    .col(ColumnDef::new(Batch::Orders).big_integer().not_null())
+                    .col(ColumnDef::new(Batch::BatcherFee).big_integer().not_null())
+                    .col(ColumnDef::new(Batch::Deposit).big_integer().not_null())
+                    .col(ColumnDef::new(Batch::TxFee).big_integer().not_null())
+                    // Executed orders with a known placement and the pairs of them executed in
+                    // the reverse order of the placement
+                    .col(ColumnDef::new(Batch::PlacedOrders).big_integer().not_null())
+                    .col(ColumnDef::new(Batch::Inversions).big_integer().not_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .table(Batch::Table)
+                    .name("index-batch-script_hash-batcher")
+                    .col(Batch::ScriptHash)
+                    .col(Batch::Batcher)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .table(Batch::Table)
+                    .name("index-batch-tx_id")
+                    .col(Batch::TxId)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Batch::Table).to_owned())
+            .await
+    }
+}
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+pub enum Batch {
+    Table,
+    Id,
+    TxId,
+    ScriptHash,
+    Batcher,
+    Orders,
+    BatcherFee,
+    Deposit,
+    TxFee,
+    PlacedOrders,
+    Inversions,
+}
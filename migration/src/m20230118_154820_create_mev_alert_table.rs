@@ -0,0 +1,101 @@
+use sea_orm_migration::prelude::*;
+
+use crate::{
+    m20221006_114228_create_transaction_table::Transaction,
+    m20221006_141624_create_token_table::Token,
+};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(MevAlert::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(MevAlert::Id)
+                            .big_integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    // The transaction of the last swap of the sequence
+                    .col(ColumnDef::new(MevAlert::TxId).big_integer().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-mev_alert-tx_id")
+                            .from(MevAlert::Table, MevAlert::TxId)
+                            .to(Transaction::Table, Transaction::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .col(ColumnDef::new(MevAlert::Kind).string().not_null())
+                    .col(ColumnDef::new(MevAlert::ScriptHash).binary().not_null())
+                    .col(ColumnDef::new(MevAlert::Token1Id).big_integer().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-mev_alert-token1_id")
+                            .from(MevAlert::Table, MevAlert::Token1Id)
+                            .to(Token::Table, Token::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .col(ColumnDef::new(MevAlert::Token2Id).big_integer().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-mev_alert-token2_id")
+                            .from(MevAlert::Table, MevAlert::Token2Id)
+                            .to(Token::Table, Token::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .col(ColumnDef::new(MevAlert::Attacker).binary().null())
+                    // JSON lists of the victim credentials and of the swap event ids
+                    .col(ColumnDef::new(MevAlert::Victims).text().not_null())
+                    .col(ColumnDef::new(MevAlert::Swaps).text().not_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .table(MevAlert::Table)
+                    .name("index-mev_alert-tx_id")
+                    .col(MevAlert::TxId)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .table(MevAlert::Table)
+                    .name("index-mev_alert-script_hash")
+                    .col(MevAlert::ScriptHash)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(MevAlert::Table).to_owned())
+            .await
+    }
+}
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+pub enum MevAlert {
+    Table,
+    Id,
+    TxId,
+    Kind,
+    ScriptHash,
+    Token1Id,
+    Token2Id,
+    Attacker,
+    Victims,
+    Swaps,
+}
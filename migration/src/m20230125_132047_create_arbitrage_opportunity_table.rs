@@ -0,0 +1,155 @@
+use sea_orm_migration::prelude::*;
+
+use crate::{
+    m20221006_141624_create_token_table::Token, m20221010_123828_create_block_table::Block,
+};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(ArbitrageOpportunity::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ArbitrageOpportunity::Id)
+                            .big_integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(ArbitrageOpportunity::BlockId)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-arbitrage_opportunity-block_id")
+                            .from(ArbitrageOpportunity::Table, ArbitrageOpportunity::BlockId)
+                            .to(Block::Table, Block::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .col(
+                        ColumnDef::new(ArbitrageOpportunity::Token1Id)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-arbitrage_opportunity-token1_id")
+                            .from(ArbitrageOpportunity::Table, ArbitrageOpportunity::Token1Id)
+                            .to(Token::Table, Token::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .col(
+                        ColumnDef::new(ArbitrageOpportunity::Token2Id)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-arbitrage_opportunity-token2_id")
+                            .from(ArbitrageOpportunity::Table, ArbitrageOpportunity::Token2Id)
+                            .to(Token::Table, Token::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .col(
+                        ColumnDef::new(ArbitrageOpportunity::BuyScriptHash)
+                            .binary()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ArbitrageOpportunity::SellScriptHash)
+                            .binary()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ArbitrageOpportunity::BuyRate)
+                            .decimal()
+                            .null(),
+                    )
+                    .col(
+                        ColumnDef::new(ArbitrageOpportunity::SellRate)
+                            .decimal()
+                            .null(),
+                    )
+                    .col(
+                        ColumnDef::new(ArbitrageOpportunity::AmountIn)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ArbitrageOpportunity::AmountBought)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ArbitrageOpportunity::AmountOut)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ArbitrageOpportunity::BatcherFee)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ArbitrageOpportunity::Profit)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .table(ArbitrageOpportunity::Table)
+                    .name("index-arbitrage_opportunity-block_id")
+                    .col(ArbitrageOpportunity::BlockId)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .table(ArbitrageOpportunity::Table)
+                    .name("index-arbitrage_opportunity-token1_id-token2_id")
+                    .col(ArbitrageOpportunity::Token1Id)
+                    .col(ArbitrageOpportunity::Token2Id)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ArbitrageOpportunity::Table).to_owned())
+            .await
+    }
+}
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+pub enum ArbitrageOpportunity {
+    Table,
+    Id,
+    BlockId,
+    Token1Id,
+    Token2Id,
+    BuyScriptHash,
+    SellScriptHash,
+    BuyRate,
+    SellRate,
+    AmountIn,
+    AmountBought,
+    AmountOut,
+    BatcherFee,
+    Profit,
+}
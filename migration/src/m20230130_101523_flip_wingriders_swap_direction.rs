@@ -0,0 +1,40 @@
+use sea_orm_migration::prelude::*;
+
+use crate::m20221024_135934_create_swap_table::Swap;
+
+// Script hash of the mainnet WingRiders V1 pool,
+// e6c90a5923713af5786963dee0fdffd830ca7e0c86a041d9e5833e91.
+// Migrations don't read the pool config, so swaps of WingRiders pools configured with another
+// script hash (e.g. on a testnet) are not migrated and have to be reindexed.
+static WINGRIDERS_V1_SCRIPT_HASH: [u8; 28] = [
+    0xe6, 0xc9, 0x0a, 0x59, 0x23, 0x71, 0x3a, 0xf5, 0x78, 0x69, 0x63, 0xde, 0xe0, 0xfd, 0xff, 0xd8,
+    0x30, 0xca, 0x7e, 0x0c, 0x86, 0xa0, 0x41, 0xd9, 0xe5, 0x83, 0x3e, 0x91,
+];
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+// WingRiders swaps were stored with `true` when the first token was sold, unlike the other DEXes,
+// so the flag is negated. Applying it twice restores the original values.
+async fn flip_direction(manager: &SchemaManager<'_>) -> Result<(), DbErr> {
+    manager
+        .exec_stmt(
+            Query::update()
+                .table(Swap::Table)
+                .value_expr(Swap::Direction, Expr::col(Swap::Direction).not())
+                .and_where(Expr::col(Swap::ScriptHash).eq(WINGRIDERS_V1_SCRIPT_HASH.to_vec()))
+                .to_owned(),
+        )
+        .await
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        flip_direction(manager).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        flip_direction(manager).await
+    }
+}
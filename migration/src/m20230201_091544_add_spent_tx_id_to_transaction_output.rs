@@ -0,0 +1,100 @@
+use sea_orm_migration::{
+    prelude::*,
+    sea_orm::{ConnectionTrait, Statement},
+};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // No foreign key, as SQLite can't add one to an existing table, the column is cleared
+        // explicitly when the spending transaction is removed
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(TransactionOutput::Table)
+                    .add_column(
+                        ColumnDef::new(TransactionOutput::SpentTxId)
+                            .big_integer()
+                            .null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .table(TransactionOutput::Table)
+                    .name("index-transaction_output-spent_tx_id")
+                    .col(TransactionOutput::SpentTxId)
+                    .to_owned(),
+            )
+            .await?;
+
+        // Plain SQL understood by both Postgres and SQLite. Spent order outputs are known from the
+        // orders, a pool output is spent by the next price update of the same pool and pair.
+        let statements = [
+            r#"UPDATE transaction_output SET spent_tx_id = (
+                SELECT o.spent_tx_id FROM "order" AS o
+                WHERE o.tx_id = transaction_output.tx_id
+                AND o.output_index = transaction_output."index"
+            )
+            WHERE EXISTS (
+                SELECT 1 FROM "order" AS o
+                WHERE o.tx_id = transaction_output.tx_id
+                AND o.output_index = transaction_output."index"
+                AND o.spent_tx_id IS NOT NULL
+            )"#,
+            r#"UPDATE transaction_output SET spent_tx_id = (
+                SELECT MIN(p2.tx_id) FROM price_update AS p
+                JOIN price_update AS p2 ON p2.script_hash = p.script_hash
+                AND p2.token1_id = p.token1_id AND p2.token2_id = p.token2_id
+                AND p2.tx_id > p.tx_id
+                WHERE p.tx_id = transaction_output.tx_id
+                AND p.output_index = transaction_output."index"
+            )
+            WHERE spent_tx_id IS NULL AND EXISTS (
+                SELECT 1 FROM price_update AS p
+                WHERE p.tx_id = transaction_output.tx_id
+                AND p.output_index = transaction_output."index"
+            )"#,
+        ];
+        let db = manager.get_connection();
+        for sql in statements.into_iter() {
+            db.execute(Statement::from_string(
+                manager.get_database_backend(),
+                sql.to_owned(),
+            ))
+            .await?;
+        }
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .table(TransactionOutput::Table)
+                    .name("index-transaction_output-spent_tx_id")
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(TransactionOutput::Table)
+                    .drop_column(TransactionOutput::SpentTxId)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+pub enum TransactionOutput {
+    Table,
+    SpentTxId,
+}
@@ -0,0 +1,61 @@
+use sea_orm_migration::{
+    prelude::*,
+    sea_orm::{ConnectionTrait, Statement},
+};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Position of the swap among the swaps of the pool in the transaction
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Swap::Table)
+                    .add_column(
+                        ColumnDef::new(Swap::Position)
+                            .big_integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // The swaps of a transaction were inserted in the order the adapters found them
+        manager
+            .get_connection()
+            .execute(Statement::from_string(
+                manager.get_database_backend(),
+                r#"UPDATE swap SET position = (
+                    SELECT COUNT(*) FROM swap AS s
+                    WHERE s.tx_id = swap.tx_id
+                    AND s.script_hash = swap.script_hash
+                    AND s.id < swap.id
+                )"#
+                .to_owned(),
+            ))
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Swap::Table)
+                    .drop_column(Swap::Position)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+pub enum Swap {
+    Table,
+    Position,
+}
@@ -0,0 +1,8 @@
+use migration::{Migrator, MigratorTrait};
+use sea_orm_migration::sea_orm::Database;
+
+#[async_std::test]
+async fn migrate_sqlite() {
+    let db = Database::connect("sqlite::memory:").await.unwrap();
+    Migrator::up(&db, None).await.unwrap();
+}
@@ -0,0 +1,54 @@
+use std::time::Duration;
+
+use sea_orm::{prelude::Date, DatabaseConnection};
+
+use crate::{candles::Interval, queries};
+
+// How often the daily mean exchange ratios are brought up to date.
+static MEAN_EXCHANGE_RATIO_PERIOD: Duration = Duration::from_secs(10 * 60);
+
+/// Parse the `YYYY-MM-DD` date into the unix timestamp of its midnight.
+pub fn parse_date(date: &str) -> anyhow::Result<i64> {
+    Ok(Date::parse_from_str(date, "%Y-%m-%d")?
+        .and_hms_opt(0, 0, 0)
+        .ok_or_else(|| anyhow::anyhow!("Invalid date"))?
+        .timestamp())
+}
+
+/// Periodically fill the `mean_exchange_ratio` table up to the day of the latest stored block.
+/// The last computed day is always recomputed, as it could have been incomplete. With
+/// `backfill_from` set, all days since then are recomputed in the first run.
+pub async fn run_mean_exchange_ratio_job(db: DatabaseConnection, backfill_from: Option<i64>) {
+    let mut backfill_from = backfill_from;
+    loop {
+        match update_mean_exchange_ratios(backfill_from, &db).await {
+            Ok(()) => backfill_from = None,
+            Err(e) => tracing::error!("Mean exchange ratio job failed: {}", e),
+        }
+        tokio::time::sleep(MEAN_EXCHANGE_RATIO_PERIOD).await;
+    }
+}
+
+async fn update_mean_exchange_ratios(
+    backfill_from: Option<i64>,
+    db: &DatabaseConnection,
+) -> anyhow::Result<()> {
+    let last_day = match queries::get_last_block_timestamp(db).await? {
+        Some(timestamp) => Interval::Day.bucket_start(timestamp),
+        None => return Ok(()),
+    };
+    let first_day = match backfill_from {
+        Some(timestamp) => Interval::Day.bucket_start(timestamp),
+        None => queries::get_last_mean_exchange_ratio_date(db)
+            .await?
+            .unwrap_or(last_day),
+    };
+
+    let mut day = first_day;
+    while day <= last_day {
+        queries::compute_mean_exchange_ratios(day, db).await?;
+        day += Interval::Day.seconds();
+    }
+    tracing::debug!("Mean exchange ratios computed up to {}", last_day);
+    Ok(())
+}
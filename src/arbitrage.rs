@@ -0,0 +1,156 @@
+use oura::model::BlockRecord;
+
+use crate::{
+    config::PoolConfig,
+    liquidity, quote,
+    types::{ArbitrageOpportunity, AssetAmount, PoolState},
+    utils,
+};
+
+/// Pool with its latest reserves.
+struct Leg<'a> {
+    pool: &'a PoolConfig,
+    state: &'a PoolState,
+}
+
+impl Leg<'_> {
+    // Lovelace kept by the batcher of an order of the pool.
+    fn batcher_fee(&self) -> u64 {
+        let fees = self.pool.as_trait().fees();
+        fees.ada_swap_in.saturating_sub(fees.ada_swap_out)
+    }
+}
+
+// Amount of the second token bought with the amount of the first one in the buy pool and the
+// amount of the first token it's sold back for in the sell pool.
+fn round_trip(buy: &Leg, sell: &Leg, amount: u64) -> Option<(u64, u64)> {
+    let bought = quote::quote_pool(
+        buy.pool,
+        buy.state.reserve1.amount,
+        buy.state.reserve2.amount,
+        amount,
+    )?
+    .amount_out;
+    let received = quote::quote_pool(
+        sell.pool,
+        sell.state.reserve2.amount,
+        sell.state.reserve1.amount,
+        bought,
+    )?
+    .amount_out;
+    Some((bought, received))
+}
+
+fn get_gain(buy: &Leg, sell: &Leg, amount: u64) -> i128 {
+    match round_trip(buy, sell, amount) {
+        Some((_, received)) => received as i128 - amount as i128,
+        None => i128::MIN,
+    }
+}
+
+// Input amount with the highest gain. The gain of the round trip is concave in the input amount
+// for both the constant product and the stableswap curves, so a ternary search finds it.
+fn get_best_amount(buy: &Leg, sell: &Leg) -> u64 {
+    let (mut low, mut high) = (0, buy.state.reserve1.amount);
+    while high - low > 2 {
+        let third = (high - low) / 3;
+        let (left, right) = (low + third, high - third);
+        if get_gain(buy, sell, left) < get_gain(buy, sell, right) {
+            low = left;
+        } else {
+            high = right;
+        }
+    }
+    (low..=high)
+        .max_by_key(|amount| get_gain(buy, sell, *amount))
+        .unwrap_or_default()
+}
+
+// Lovelace amount in units of the first token of the pair, priced at the pool of the token with
+// the most ADA. ADA is the first token of every pair it's part of. Returns `None` when the token
+// has no ADA pool.
+fn get_first_token_amount(states: &[PoolState], pair: &PoolState, lovelace: u64) -> Option<u64> {
+    let is_ada = |a: &AssetAmount| a.asset.policy_id.is_empty() && a.asset.name.is_empty();
+    if is_ada(&pair.reserve1) {
+        return Some(lovelace);
+    }
+    let pool = states
+        .iter()
+        .filter(|s| s.asset2 == pair.asset1 && is_ada(&s.reserve1) && s.reserve1.amount > 0)
+        .max_by_key(|s| s.reserve1.amount)?;
+    let amount = lovelace as u128 * pool.reserve2.amount as u128 / pool.reserve1.amount as u128;
+    u64::try_from(amount).ok()
+}
+
+fn get_opportunity(
+    buy: &Leg,
+    sell: &Leg,
+    states: &[PoolState],
+    block: &BlockRecord,
+    timestamp: i64,
+) -> Option<ArbitrageOpportunity> {
+    let amount_in = get_best_amount(buy, sell);
+    let (amount_bought, amount_out) = round_trip(buy, sell, amount_in)?;
+    let batcher_fee = buy.batcher_fee() + sell.batcher_fee();
+    // Opportunities whose batcher fees can't be priced in the first token are left out
+    let fee = get_first_token_amount(states, buy.state, batcher_fee)?;
+    let profit = amount_out as i64 - amount_in as i64 - fee as i64;
+    if profit <= 0 {
+        return None;
+    }
+
+    let pair = (buy.state.asset1, buy.state.asset2);
+    Some(ArbitrageOpportunity {
+        event_id: utils::get_arbitrage_event_id(
+            &block.hash,
+            pair,
+            &buy.state.script_hash,
+            &sell.state.script_hash,
+        ),
+        asset1: pair.0,
+        asset2: pair.1,
+        buy_pool: buy.state.script_hash.clone(),
+        sell_pool: sell.state.script_hash.clone(),
+        buy_rate: utils::get_rate(buy.state.reserve1.amount, buy.state.reserve2.amount),
+        sell_rate: utils::get_rate(sell.state.reserve1.amount, sell.state.reserve2.amount),
+        amount_in,
+        amount_bought,
+        amount_out,
+        batcher_fee,
+        profit,
+        block_hash: block.hash.clone(),
+        slot: block.slot,
+        timestamp: Some(timestamp),
+    })
+}
+
+/// Profitable round trips between the pools of the pairs with the latest reserves. Only the pairs
+/// whose pools changed in the block are compared, the others were compared before.
+pub fn find(
+    pairs: &[(i64, i64)],
+    pools: &[&PoolConfig],
+    block: &BlockRecord,
+    timestamp: i64,
+) -> Vec<ArbitrageOpportunity> {
+    let states = liquidity::get_pool_states();
+    let mut opportunities = Vec::new();
+    for pair in pairs.iter() {
+        let legs: Vec<Leg> = states
+            .iter()
+            .filter(|s| (s.asset1, s.asset2) == *pair)
+            .filter_map(|state| {
+                let pool = *pools.iter().find(|p| p.script_hash == state.script_hash)?;
+                Some(Leg { pool, state })
+            })
+            .collect();
+        for buy in legs.iter() {
+            for sell in legs.iter() {
+                if buy.state.script_hash == sell.state.script_hash {
+                    continue;
+                }
+                opportunities.extend(get_opportunity(buy, sell, &states, block, timestamp));
+            }
+        }
+    }
+    opportunities
+}
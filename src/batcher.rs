@@ -0,0 +1,106 @@
+use oura::model::TransactionRecord;
+use pallas::crypto::hash::Hasher;
+use sea_orm::DatabaseConnection;
+
+use crate::{
+    cache::UtxoCache,
+    config::PoolConfig,
+    queries,
+    types::{BatchInfo, OutputReference, Swap},
+    utils,
+};
+
+/// Key hash of the batcher, the first signer of the transaction. Batchers pay the transaction
+/// fee and the collateral from their own wallet, so they always sign the batch.
+pub fn get_batcher(transaction: &TransactionRecord) -> Option<Vec<u8>> {
+    let witness = transaction.vkey_witnesses.as_ref()?.first()?;
+    let vkey = hex::decode(&witness.vkey_hex).ok()?;
+    Some(Hasher::<224>::hash(&vkey).to_vec())
+}
+
+// Pairs of the orders executed in the reverse order of their placement.
+fn count_inversions(placements: &[(i64, i64)]) -> u64 {
+    placements
+        .iter()
+        .enumerate()
+        .map(|(i, first)| placements[i + 1..].iter().filter(|p| *p < first).count() as u64)
+        .sum()
+}
+
+/// Record the batch which executed the swaps of the pool. The swaps come in the order the batch
+/// processes the orders.
+///
+/// The batcher fee is the lovelace of the spent orders and of the pool input which neither went
+/// to the pool nor was paid out to the traders, the batcher's own wallet is left out. The
+/// deposit is the lovelace paid out on top of the lovelace bought by the swaps.
+pub async fn record(
+    tx_id: i64,
+    pool: &PoolConfig,
+    utxos: &UtxoCache,
+    transaction: &TransactionRecord,
+    swaps: &[Swap],
+    timestamp: i64,
+    db: &DatabaseConnection,
+) -> anyhow::Result<()> {
+    let script_hash = hex::decode(&pool.script_hash)?;
+    let request_hash = hex::decode(&pool.request_hash)?;
+    let batcher = get_batcher(transaction);
+    let inputs = utxos
+        .resolve(transaction.inputs.as_deref().unwrap_or_default())
+        .await?;
+
+    // Orders of every kind spent by the batch, liquidity orders included
+    let mut spent_orders = 0;
+    let mut input_amount = 0;
+    for input in inputs.iter().flatten() {
+        let hash = utils::get_payment_hash(&input.address);
+        if hash.as_ref() == Some(&request_hash) {
+            spent_orders += 1;
+            input_amount += input.amount;
+        } else if hash.as_ref() == Some(&script_hash) {
+            input_amount += input.amount;
+        }
+    }
+    let mut pool_amount = 0;
+    let mut payout_amount = 0;
+    for output in transaction.outputs.iter().flatten() {
+        let hash = utils::get_payment_hash(&output.address);
+        if hash.as_ref() == Some(&script_hash) {
+            pool_amount += output.amount;
+        } else if hash.is_some() && hash != batcher && hash.as_ref() != Some(&request_hash) {
+            payout_amount += output.amount;
+        }
+    }
+    let bought_amount: u64 = swaps
+        .iter()
+        .filter_map(|s| {
+            let bought = match s.direction {
+                false => &s.second,
+                true => &s.first,
+            };
+            (bought.asset.policy_id.is_empty() && bought.asset.name.is_empty())
+                .then_some(bought.amount)
+        })
+        .sum();
+
+    let orders: Vec<OutputReference> = swaps.iter().filter_map(|s| s.order.clone()).collect();
+    let placements = queries::get_order_placements(&orders, db).await?;
+    let placed: Vec<(i64, i64)> = orders
+        .iter()
+        .filter_map(|o| placements.get(&(o.tx_hash.clone(), o.index)).copied())
+        .collect();
+
+    let batch = BatchInfo {
+        script_hash: pool.script_hash.clone(),
+        tx_hash: transaction.hash.clone(),
+        batcher: batcher.map(hex::encode),
+        orders: spent_orders,
+        batcher_fee: input_amount.saturating_sub(pool_amount + payout_amount),
+        deposit: payout_amount.saturating_sub(bought_amount),
+        tx_fee: transaction.fee,
+        placed_orders: placed.len() as u64,
+        inversions: count_inversions(&placed),
+        timestamp: Some(timestamp),
+    };
+    queries::insert_batch(tx_id, &batch, db).await
+}
@@ -0,0 +1,292 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::RwLock,
+};
+
+use oura::model::{TransactionRecord, TxInputRecord, TxOutputRecord};
+use sea_orm::DatabaseConnection;
+
+use crate::{
+    config::PoolConfig,
+    queries, registry,
+    types::{Asset, AssetInfo, TokenMetadata},
+    utils,
+};
+
+// Number of blocks we are able to roll back (the security parameter k of the mainnet).
+pub static ROLLBACK_DEPTH: usize = 2160;
+
+type OutputRef = (String, u64);
+
+struct BlockChanges {
+    slot: u64,
+    created: Vec<OutputRef>,
+    spent: Vec<(OutputRef, TxOutputRecord)>,
+}
+
+/// Bounded in-memory index of unspent outputs sitting on the watched addresses.
+///
+/// The cache is populated from the database on startup and then kept up to date from the chain.
+/// Changes of the last `ROLLBACK_DEPTH` blocks are remembered, so rollbacks can be reverted.
+/// Without a database the cache is the only source of UTxOs, so only outputs created after the
+/// start are known.
+pub struct UtxoCache {
+    db: Option<DatabaseConnection>,
+    capacity: usize,
+    hashes: HashSet<Vec<u8>>,
+    addresses: HashSet<String>,
+    outputs: HashMap<OutputRef, TxOutputRecord>,
+    insertion_order: VecDeque<OutputRef>,
+    changes: VecDeque<BlockChanges>,
+}
+
+impl UtxoCache {
+    pub fn new(pools: &[&PoolConfig], capacity: usize, db: Option<DatabaseConnection>) -> Self {
+        let mut hashes = HashSet::new();
+        let mut addresses = HashSet::new();
+        for pool in pools.iter() {
+            hashes.insert(hex::decode(&pool.script_hash).unwrap());
+            hashes.insert(hex::decode(&pool.request_hash).unwrap());
+            hashes.insert(hex::decode(&pool.vesting_hash).unwrap());
+            addresses.insert(pool.address.clone());
+        }
+        UtxoCache {
+            db,
+            capacity,
+            hashes,
+            addresses,
+            outputs: HashMap::new(),
+            insertion_order: VecDeque::new(),
+            changes: VecDeque::new(),
+        }
+    }
+
+    pub fn is_watched(&self, output: &TxOutputRecord) -> bool {
+        self.addresses.contains(&output.address)
+            || utils::get_payment_hash(&output.address)
+                .map(|hash| self.hashes.contains(&hash))
+                .unwrap_or(false)
+    }
+
+    /// Whether the transaction spends a cached watched output, e.g. an order cancelled by its
+    /// owner, which creates no watched output itself.
+    pub fn spends_watched(&self, transaction: &TransactionRecord) -> bool {
+        transaction
+            .inputs
+            .iter()
+            .flatten()
+            .any(|i| self.outputs.contains_key(&(i.tx_id.clone(), i.index)))
+    }
+
+    /// Fill the cache with the latest unspent order and pool outputs stored in the database.
+    pub async fn load(&mut self) -> anyhow::Result<()> {
+        let outputs = match &self.db {
+            Some(db) => queries::get_latest_outputs(self.capacity as u64, db).await?,
+            None => return Ok(()),
+        };
+        // Outputs come newest first, insert them oldest first so the newest are evicted last
+        for (output_ref, output) in outputs.into_iter().rev() {
+            if self.is_watched(&output) {
+                self.insert(output_ref, output);
+            }
+        }
+        tracing::info!("UTxO cache loaded with {} outputs", self.outputs.len());
+        Ok(())
+    }
+
+    /// Resolve transaction inputs to the outputs they spend. Only the inputs missing in the cache
+    /// are looked up in the database, all of them in a single query.
+    pub async fn resolve(
+        &self,
+        inputs: &[TxInputRecord],
+    ) -> anyhow::Result<Vec<Option<TxOutputRecord>>> {
+        let mut result: Vec<Option<TxOutputRecord>> = inputs
+            .iter()
+            .map(|i| self.outputs.get(&(i.tx_id.clone(), i.index)).cloned())
+            .collect();
+
+        let missing: Vec<TxInputRecord> = inputs
+            .iter()
+            .zip(result.iter())
+            .filter(|(_, o)| o.is_none())
+            .map(|(i, _)| i.clone())
+            .collect();
+        let db = match &self.db {
+            Some(db) if !missing.is_empty() => db,
+            _ => return Ok(result),
+        };
+
+        let mut found = queries::get_utxo_input(&missing, db).await?.into_iter();
+        for output in result.iter_mut().filter(|o| o.is_none()) {
+            *output = found.next().flatten();
+        }
+        Ok(result)
+    }
+
+    pub fn start_block(&mut self, slot: u64) {
+        self.changes.push_back(BlockChanges {
+            slot,
+            created: Vec::new(),
+            spent: Vec::new(),
+        });
+        while self.changes.len() > ROLLBACK_DEPTH {
+            self.changes.pop_front();
+        }
+    }
+
+    /// Spend the transaction inputs and add its watched outputs. A transaction which failed
+    /// phase-2 validation spends its collateral inputs instead of the regular ones.
+    pub fn apply_transaction(&mut self, transaction: &TransactionRecord, valid: bool) {
+        let inputs = match valid {
+            true => &transaction.inputs,
+            false => &transaction.collateral_inputs,
+        };
+        for input in inputs.iter().flatten() {
+            let output_ref = (input.tx_id.clone(), input.index);
+            if let Some(output) = self.outputs.remove(&output_ref) {
+                if let Some(changes) = self.changes.back_mut() {
+                    changes.spent.push((output_ref, output));
+                }
+            }
+        }
+
+        for (index, output) in utils::get_produced_outputs(transaction, valid) {
+            if !self.is_watched(output) {
+                continue;
+            }
+            let output_ref = (transaction.hash.clone(), index as u64);
+            if let Some(changes) = self.changes.back_mut() {
+                changes.created.push(output_ref.clone());
+            }
+            self.insert(output_ref, output.clone());
+        }
+    }
+
+    /// Revert changes of all blocks after the given slot.
+    pub fn rollback_to_slot(&mut self, slot: u64) {
+        while matches!(self.changes.back(), Some(changes) if changes.slot > slot) {
+            let changes = self.changes.pop_back().unwrap();
+            for output_ref in changes.created.iter() {
+                self.outputs.remove(output_ref);
+            }
+            for (output_ref, output) in changes.spent.into_iter() {
+                self.insert(output_ref, output);
+            }
+        }
+    }
+
+    fn insert(&mut self, output_ref: OutputRef, output: TxOutputRecord) {
+        self.insertion_order.push_back(output_ref.clone());
+        self.outputs.insert(output_ref, output);
+
+        // Evict the oldest outputs, the order can also contain already spent ones
+        while self.outputs.len() > self.capacity {
+            match self.insertion_order.pop_front() {
+                Some(oldest) => {
+                    self.outputs.remove(&oldest);
+                }
+                None => break,
+            }
+        }
+        if self.insertion_order.len() > 2 * self.capacity {
+            let outputs = &self.outputs;
+            self.insertion_order.retain(|o| outputs.contains_key(o));
+        }
+    }
+}
+
+// Tokens known only in memory with their on-chain metadata, the token id is the index + 1, negated
+// next to a database.
+static MEMORY_TOKENS: RwLock<Vec<(Asset, TokenMetadata)>> = RwLock::new(Vec::new());
+
+// Register the token in memory and return its index + 1.
+fn register_memory_token(asset: &Asset) -> i64 {
+    let mut tokens = MEMORY_TOKENS.write().unwrap();
+    tokens.push((asset.clone(), TokenMetadata::default()));
+    tokens.len() as i64
+}
+
+/// Assets registered in memory, in the same shape as `queries::get_assets`. Next to a database
+/// the ids are negative, as assigned by `TokenRegistry::get_id`.
+pub fn get_memory_assets(with_db: bool) -> HashMap<i64, AssetInfo> {
+    MEMORY_TOKENS
+        .read()
+        .unwrap()
+        .iter()
+        .enumerate()
+        .map(|(i, (a, m))| {
+            let id = (i + 1) as i64;
+            (
+                match with_db {
+                    true => -id,
+                    false => id,
+                },
+                AssetInfo {
+                    asset: a.clone(),
+                    metadata: m.clone().merge(registry::get(a).unwrap_or_default()),
+                },
+            )
+        })
+        .collect()
+}
+
+/// Token ids used in the API. With a database they are read from the `token` table, otherwise
+/// they are assigned in memory in the order the tokens are first seen.
+///
+/// Without the persistent mode the database is only read, so the tokens which are not stored yet
+/// are assigned negative ids in memory, which can't clash with the stored ones.
+pub struct TokenRegistry {
+    db: Option<DatabaseConnection>,
+    persistent: bool,
+    ids: HashMap<(String, String), i64>,
+}
+
+impl TokenRegistry {
+    pub fn new(db: Option<DatabaseConnection>, persistent: bool) -> Self {
+        TokenRegistry {
+            db,
+            persistent,
+            ids: HashMap::new(),
+        }
+    }
+
+    pub async fn get_id(&mut self, asset: &Asset) -> anyhow::Result<i64> {
+        let key = (asset.policy_id.clone(), asset.name.clone());
+        if let Some(id) = self.ids.get(&key) {
+            return Ok(*id);
+        }
+        let id = match &self.db {
+            Some(db) => match queries::get_token_id(asset, db).await? {
+                Some(id) => id,
+                None if self.persistent => return Err(anyhow::anyhow!("Token not found")),
+                None => -register_memory_token(asset),
+            },
+            None => register_memory_token(asset),
+        };
+        self.ids.insert(key, id);
+        Ok(id)
+    }
+
+    /// Update metadata of a known token from the chain, unknown tokens are ignored.
+    pub async fn update_metadata(
+        &mut self,
+        asset: &Asset,
+        metadata: TokenMetadata,
+    ) -> anyhow::Result<()> {
+        match &self.db {
+            Some(db) if self.persistent => {
+                queries::update_onchain_token_metadata(asset, metadata, db).await?
+            }
+            _ => {
+                let mut tokens = MEMORY_TOKENS.write().unwrap();
+                if let Some((_, stored)) = tokens
+                    .iter_mut()
+                    .find(|(a, _)| a.policy_id == asset.policy_id && a.name == asset.name)
+                {
+                    *stored = metadata.merge(stored.clone());
+                }
+            }
+        }
+        Ok(())
+    }
+}
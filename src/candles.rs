@@ -0,0 +1,123 @@
+use std::collections::HashSet;
+
+use sea_orm::{prelude::Decimal, DatabaseConnection};
+
+use crate::queries;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Interval {
+    Minute,
+    FiveMinutes,
+    Hour,
+    Day,
+    Week,
+}
+
+pub static INTERVALS: [Interval; 5] = [
+    Interval::Minute,
+    Interval::FiveMinutes,
+    Interval::Hour,
+    Interval::Day,
+    Interval::Week,
+];
+
+// 1970-01-01 was Thursday, weekly candles start on Monday.
+static WEEK_OFFSET: i64 = 4 * 86_400;
+
+impl Interval {
+    pub fn seconds(&self) -> i64 {
+        match self {
+            Interval::Minute => 60,
+            Interval::FiveMinutes => 5 * 60,
+            Interval::Hour => 3_600,
+            Interval::Day => 86_400,
+            Interval::Week => 7 * 86_400,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Interval::Minute => "1m",
+            Interval::FiveMinutes => "5m",
+            Interval::Hour => "1h",
+            Interval::Day => "1d",
+            Interval::Week => "1w",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Interval> {
+        INTERVALS.iter().find(|i| i.name() == name).copied()
+    }
+
+    /// Unix timestamp of the start of the candle containing the given timestamp.
+    pub fn bucket_start(&self, timestamp: i64) -> i64 {
+        let offset = match self {
+            Interval::Week => WEEK_OFFSET,
+            _ => 0,
+        };
+        (timestamp - offset).div_euclid(self.seconds()) * self.seconds() + offset
+    }
+}
+
+/// Candle series identification: pool script hash and the token pair. The series with an empty
+/// script hash aggregates all pools of the pair.
+pub type SeriesKey = (Vec<u8>, i64, i64);
+
+fn get_series(key: &SeriesKey) -> [SeriesKey; 2] {
+    let (_, token1_id, token2_id) = key;
+    [key.clone(), (Vec::new(), *token1_id, *token2_id)]
+}
+
+/// Add a new pool price together with the volume swapped in the same transaction to all candles
+/// of the pool and of the pair containing the timestamp.
+pub async fn update(
+    key: &SeriesKey,
+    timestamp: i64,
+    price: Decimal,
+    volume: (i64, i64),
+    trades: i64,
+    db: &DatabaseConnection,
+) -> anyhow::Result<()> {
+    for series in get_series(key).iter() {
+        for interval in INTERVALS.iter() {
+            queries::add_to_candle(
+                series,
+                interval.name(),
+                interval.bucket_start(timestamp),
+                price,
+                volume,
+                trades,
+                db,
+            )
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Rebuild all candles containing the given timestamps from the stored price updates and swaps.
+/// Used when the underlying rows were removed or replaced, e.g. on rollback.
+pub async fn recompute(
+    affected: &[(SeriesKey, i64)],
+    db: &DatabaseConnection,
+) -> anyhow::Result<()> {
+    let mut buckets = HashSet::new();
+    for (key, timestamp) in affected.iter() {
+        for series in get_series(key) {
+            for interval in INTERVALS.iter() {
+                buckets.insert((series.clone(), *interval, interval.bucket_start(*timestamp)));
+            }
+        }
+    }
+    for (series, interval, start) in buckets.iter() {
+        queries::recompute_candle(
+            series,
+            interval.name(),
+            *start,
+            start + interval.seconds(),
+            db,
+        )
+        .await?;
+    }
+    Ok(())
+}
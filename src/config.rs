@@ -1,18 +1,52 @@
-use crate::sink::common::Dex;
+use crate::{
+    cache::UtxoCache,
+    sink::common::{Dex, Fees, OrderDatum},
+};
+use rust_decimal::Decimal;
 use serde::Deserialize;
 
-use crate::types::{AssetAmount, Swap};
+use crate::types::{Asset, AssetAmount, Swap};
 use async_trait::async_trait;
 use oura::model::TransactionRecord;
-use sea_orm::DatabaseConnection;
 
 fn default_as_true() -> bool {
     true
 }
 
+fn default_min_liquidity_share() -> Decimal {
+    Decimal::new(1, 2)
+}
+
 #[derive(Deserialize, Debug)]
 pub struct Config {
     pub pools: Vec<PoolConfig>,
+    #[serde(default)]
+    pub aggregation: AggregationConfig,
+    /// Tokens pegged to USD used for the USD prices
+    #[serde(default)]
+    pub stablecoins: Vec<Asset>,
+}
+
+/// Rules excluding outlier pools from the aggregated price of a pair.
+#[derive(Deserialize, Debug)]
+pub struct AggregationConfig {
+    /// Pools holding a smaller share of the first token reserves of the pair are excluded
+    #[serde(default = "default_min_liquidity_share")]
+    pub min_liquidity_share: Decimal,
+    /// Pools not updated for more seconds are excluded
+    pub max_age: Option<i64>,
+    /// Pools whose price deviates relatively more from the aggregated one are excluded
+    pub max_deviation: Option<Decimal>,
+}
+
+impl Default for AggregationConfig {
+    fn default() -> Self {
+        AggregationConfig {
+            min_liquidity_share: default_min_liquidity_share(),
+            max_age: None,
+            max_deviation: None,
+        }
+    }
 }
 
 #[derive(Deserialize, Debug, PartialEq, Eq)]
@@ -23,8 +57,6 @@ pub struct MinSwapV1;
 pub struct MinSwapV2;
 #[derive(Deserialize, Debug, PartialEq, Eq)]
 pub struct SundaeSwapV1;
-#[derive(Deserialize, Debug, PartialEq, Eq)]
-pub struct Empty;
 
 #[derive(Debug, Deserialize, PartialEq, Eq)]
 pub enum PoolType {
@@ -34,23 +66,34 @@ pub enum PoolType {
     MinSwapV2,
 }
 
+// MinSwap V2 has no adapter yet, so its pools are ignored: they have no fees, orders, prices or
+// swaps.
 #[async_trait]
-impl Dex for Empty {
+impl Dex for MinSwapV2 {
+    fn fees(&self) -> Fees {
+        Fees {
+            swap_fee: 0,
+            ada_swap_in: 0,
+            ada_swap_out: 0,
+        }
+    }
+    fn order(&self, _datum: &serde_json::Value) -> OrderDatum {
+        OrderDatum::default()
+    }
     async fn mean_value(
         &self,
         _pool: &PoolConfig,
-        _db: &DatabaseConnection,
         _transaction: &TransactionRecord,
     ) -> Option<(AssetAmount, AssetAmount)> {
-        unimplemented!();
+        None
     }
     async fn swaps(
         &self,
         _pool: &PoolConfig,
-        _db: &DatabaseConnection,
+        _utxos: &UtxoCache,
         _transaction: &TransactionRecord,
     ) -> anyhow::Result<Vec<Swap>> {
-        unimplemented!();
+        Ok(Vec::new())
     }
 }
 
@@ -64,6 +107,8 @@ pub struct PoolConfig {
     pub address: String,
     #[serde(rename = "type")]
     pub pool_type: PoolType,
+    /// Amplification coefficient of stableswap pools, constant product pools don't have any
+    pub amplification: Option<u64>,
 }
 
 impl PoolConfig {
@@ -72,7 +117,7 @@ impl PoolConfig {
             PoolType::WingRidersV1 => &WingRidersV1 {},
             PoolType::MinSwapV1 => &MinSwapV1 {},
             PoolType::SundaeSwapV1 => &SundaeSwapV1 {},
-            _ => &Empty {},
+            PoolType::MinSwapV2 => &MinSwapV2 {},
         }
     }
 }
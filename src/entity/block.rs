@@ -13,6 +13,7 @@ pub struct Model {
     pub epoch: i64,
     pub slot: i64,
     pub previous_block_id: Option<i64>,
+    pub timestamp: Option<DateTime>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
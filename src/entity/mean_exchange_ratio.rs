@@ -9,7 +9,8 @@ pub struct Model {
     pub id: i32,
     pub token1: i64,
     pub token2: i64,
-    pub ratio: f32,
+    #[sea_orm(column_type = "Decimal(None)")]
+    pub ratio: Decimal,
     pub date: DateTime,
 }
 
@@ -1,9 +1,13 @@
 //! SeaORM Entity. Generated by sea-orm-codegen 0.9.3
 
-pub mod prelude;
-
 pub mod address;
+pub mod arbitrage_opportunity;
+pub mod batch;
 pub mod block;
+pub mod candle;
+pub mod mean_exchange_ratio;
+pub mod mev_alert;
+pub mod order;
 pub mod price_update;
 pub mod swap;
 pub mod token;
@@ -1,10 +0,0 @@
-//! SeaORM Entity. Generated by sea-orm-codegen 0.9.3
-
-pub use super::address::Entity as Address;
-pub use super::block::Entity as Block;
-pub use super::price_update::Entity as PriceUpdate;
-pub use super::swap::Entity as Swap;
-pub use super::token::Entity as Token;
-pub use super::token_transfer::Entity as TokenTransfer;
-pub use super::transaction::Entity as Transaction;
-pub use super::transaction_output::Entity as TransactionOutput;
@@ -14,6 +14,15 @@ pub struct Model {
     pub amount1: i64,
     pub amount2: i64,
     pub direction: bool,
+    pub payment_credential: Option<Vec<u8>>,
+    pub stake_credential: Option<Vec<u8>>,
+    pub order_tx_hash: Option<Vec<u8>>,
+    pub order_index: Option<i64>,
+    pub min_receive: Option<i64>,
+    pub deadline: Option<DateTime>,
+    #[sea_orm(column_type = "Decimal(None)", nullable)]
+    pub slippage: Option<Decimal>,
+    pub position: i64,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
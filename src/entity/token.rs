@@ -9,6 +9,10 @@ pub struct Model {
     pub id: i64,
     pub policy_id: Vec<u8>,
     pub name: Vec<u8>,
+    pub decimals: Option<i32>,
+    pub ticker: Option<String>,
+    pub display_name: Option<String>,
+    pub logo: Option<String>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
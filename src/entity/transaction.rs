@@ -10,6 +10,7 @@ pub struct Model {
     #[sea_orm(unique)]
     pub hash: Vec<u8>,
     pub block_id: i64,
+    pub valid: bool,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -11,6 +11,7 @@ pub struct Model {
     pub index: i32,
     pub address_id: i64,
     pub datum_hash: Option<String>,
+    pub spent_tx_id: Option<i64>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -0,0 +1,212 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::RwLock,
+};
+
+use rust_decimal::Decimal;
+use sea_orm::DatabaseConnection;
+
+use crate::{
+    cache::ROLLBACK_DEPTH,
+    config::AggregationConfig,
+    queries, registry,
+    types::{AggregatedPrice, PoolState},
+    utils,
+};
+
+// Pool identification: the script hash and the token pair in the canonical order.
+type PoolKey = (String, i64, i64);
+
+// Latest state of every pool seen.
+static POOL_STATES: RwLock<Option<HashMap<PoolKey, PoolState>>> = RwLock::new(None);
+
+// Latest aggregated price of every pair.
+static AGGREGATED_PRICES: RwLock<Option<HashMap<(i64, i64), AggregatedPrice>>> = RwLock::new(None);
+
+// Values replaced in a block, `None` when there was no value before.
+struct BlockChanges {
+    slot: u64,
+    states: Vec<(PoolKey, Option<PoolState>)>,
+    prices: Vec<((i64, i64), Option<AggregatedPrice>)>,
+}
+
+// Changes of the last `ROLLBACK_DEPTH` blocks, so rollbacks can be reverted without a database.
+static CHANGES: RwLock<VecDeque<BlockChanges>> = RwLock::new(VecDeque::new());
+
+fn get_key(state: &PoolState) -> PoolKey {
+    (state.script_hash.clone(), state.asset1, state.asset2)
+}
+
+/// Replace the pool states with the latest ones stored in the database and recompute all
+/// aggregated prices. Used on startup and after rollbacks.
+pub async fn load(db: &DatabaseConnection, rules: &AggregationConfig) -> anyhow::Result<()> {
+    let states = queries::get_latest_pool_states(db).await?;
+    let now = states.iter().map(|s| s.timestamp).max().unwrap_or_default();
+    let pairs: HashSet<(i64, i64)> = states.iter().map(|s| (s.asset1, s.asset2)).collect();
+    *POOL_STATES.write().unwrap() = Some(states.into_iter().map(|s| (get_key(&s), s)).collect());
+
+    let aggregated = pairs
+        .into_iter()
+        .filter_map(|pair| Some((pair, aggregate(pair, now, rules)?)))
+        .collect();
+    *AGGREGATED_PRICES.write().unwrap() = Some(aggregated);
+    CHANGES.write().unwrap().clear();
+    Ok(())
+}
+
+pub fn start_block(slot: u64) {
+    let mut changes = CHANGES.write().unwrap();
+    changes.push_back(BlockChanges {
+        slot,
+        states: Vec::new(),
+        prices: Vec::new(),
+    });
+    while changes.len() > ROLLBACK_DEPTH {
+        changes.pop_front();
+    }
+}
+
+/// Revert the pool states and aggregated prices changed in all blocks after the given slot.
+pub fn rollback_to_slot(slot: u64) {
+    let mut changes = CHANGES.write().unwrap();
+    let mut states = POOL_STATES.write().unwrap();
+    let states = states.get_or_insert_with(HashMap::new);
+    let mut prices = AGGREGATED_PRICES.write().unwrap();
+    let prices = prices.get_or_insert_with(HashMap::new);
+    while matches!(changes.back(), Some(block) if block.slot > slot) {
+        let block = changes.pop_back().unwrap();
+        for (key, state) in block.states.into_iter().rev() {
+            match state {
+                Some(state) => states.insert(key, state),
+                None => states.remove(&key),
+            };
+        }
+        for (pair, price) in block.prices.into_iter().rev() {
+            match price {
+                Some(price) => prices.insert(pair, price),
+                None => prices.remove(&pair),
+            };
+        }
+    }
+}
+
+/// Store the new state of the pool and recompute the aggregated price of its pair.
+pub fn update(state: PoolState, rules: &AggregationConfig) -> Option<AggregatedPrice> {
+    let pair = (state.asset1, state.asset2);
+    let now = state.timestamp;
+    let key = get_key(&state);
+    let previous = POOL_STATES
+        .write()
+        .unwrap()
+        .get_or_insert_with(HashMap::new)
+        .insert(key.clone(), state);
+    if let Some(block) = CHANGES.write().unwrap().back_mut() {
+        block.states.push((key, previous));
+    }
+
+    let price = aggregate(pair, now, rules)?;
+    let previous = AGGREGATED_PRICES
+        .write()
+        .unwrap()
+        .get_or_insert_with(HashMap::new)
+        .insert(pair, price.clone());
+    if let Some(block) = CHANGES.write().unwrap().back_mut() {
+        block.prices.push((pair, previous));
+    }
+    Some(price)
+}
+
+/// Latest known state of the pool of the pair.
+pub fn get_pool_state(script_hash: &str, asset1: i64, asset2: i64) -> Option<PoolState> {
+    POOL_STATES
+        .read()
+        .unwrap()
+        .as_ref()?
+        .get(&(script_hash.to_string(), asset1, asset2))
+        .cloned()
+}
+
+pub fn get_pool_states() -> Vec<PoolState> {
+    POOL_STATES
+        .read()
+        .unwrap()
+        .iter()
+        .flat_map(|s| s.values().cloned())
+        .collect()
+}
+
+pub fn get_aggregated_prices() -> Vec<AggregatedPrice> {
+    AGGREGATED_PRICES
+        .read()
+        .unwrap()
+        .iter()
+        .flat_map(|p| p.values().cloned())
+        .collect()
+}
+
+// Average of the pool prices weighted by the first token reserves.
+fn get_weighted_price(pools: &[(&PoolState, Decimal)]) -> Option<Decimal> {
+    let (weighted, total) = pools.iter().try_fold(
+        (Decimal::ZERO, Decimal::ZERO),
+        |(weighted, total), (state, price)| {
+            let reserve = Decimal::from(state.reserve1.amount);
+            Some((
+                weighted.checked_add(reserve.checked_mul(*price)?)?,
+                total.checked_add(reserve)?,
+            ))
+        },
+    )?;
+    weighted.checked_div(total)
+}
+
+/// Price of the pair over all its pools weighted by their reserves. The current time is the
+/// time of the latest block, so stale pools are detected in the same way when syncing.
+fn aggregate(pair: (i64, i64), now: i64, rules: &AggregationConfig) -> Option<AggregatedPrice> {
+    let states = POOL_STATES.read().unwrap();
+    let mut pools: Vec<(&PoolState, Decimal)> = states
+        .as_ref()?
+        .values()
+        .filter(|s| (s.asset1, s.asset2) == pair)
+        .filter(|s| match rules.max_age {
+            Some(max_age) => now - s.timestamp <= max_age,
+            None => true,
+        })
+        .filter_map(|s| Some((s, utils::get_rate(s.reserve1.amount, s.reserve2.amount)?)))
+        .collect();
+
+    let total: Decimal = pools
+        .iter()
+        .map(|(s, _)| Decimal::from(s.reserve1.amount))
+        .sum();
+    let min_reserve = total * rules.min_liquidity_share;
+    pools.retain(|(s, _)| Decimal::from(s.reserve1.amount) >= min_reserve);
+
+    if let (Some(max_deviation), Some(reference)) =
+        (rules.max_deviation, get_weighted_price(&pools))
+    {
+        pools.retain(|(_, price)| {
+            (*price - reference)
+                .abs()
+                .checked_div(reference)
+                .map(|deviation| deviation <= max_deviation)
+                .unwrap_or(true)
+        });
+    }
+
+    let (first, _) = pools.first()?;
+    let rate = get_weighted_price(&pools);
+    Some(AggregatedPrice {
+        asset1: pair.0,
+        asset2: pair.1,
+        rate,
+        adjusted_rate: utils::get_adjusted_rate(
+            rate,
+            registry::get(&first.reserve1.asset).and_then(|m| m.decimals),
+            registry::get(&first.reserve2.asset).and_then(|m| m.decimals),
+        ),
+        pools: pools.iter().map(|(s, _)| s.script_hash.clone()).collect(),
+        reserve1: pools.iter().map(|(s, _)| s.reserve1.amount).sum(),
+        reserve2: pools.iter().map(|(s, _)| s.reserve2.amount).sum(),
+        timestamp: pools.iter().map(|(s, _)| s.timestamp).max()?,
+    })
+}
@@ -3,12 +3,26 @@ use sea_orm::Database;
 use std::fs;
 use tracing_subscriber::prelude::*;
 
+mod aggregation;
+mod arbitrage;
+mod batcher;
+mod cache;
+mod candles;
 mod config;
 mod entity;
+mod liquidity;
+mod mempool;
+mod metadata;
+mod mev;
+mod orderbook;
 mod queries;
+mod quote;
+mod registry;
+mod routing;
 mod server;
 mod setup;
 mod sink;
+mod stats;
 mod types;
 mod utils;
 
@@ -16,6 +30,7 @@ use hyper::service::{make_service_fn, service_fn};
 use hyper::Server;
 use std::convert::Infallible;
 use std::net::SocketAddr;
+use std::sync::Arc;
 use tokio::sync::broadcast;
 
 #[derive(Parser, Debug)]
@@ -29,11 +44,11 @@ struct Args {
     #[arg(short, long)]
     socket: String,
 
-    // Postgres connection string
+    // Postgres connection string, without it only the websocket feed is served
     #[arg(short, long)]
-    database: String,
+    database: Option<String>,
 
-    // Persistency option
+    // Persistency option, requires the database
     #[arg(short, long)]
     persistent: bool,
 
@@ -44,12 +59,28 @@ struct Args {
     /// Config file
     #[arg(short, long, default_value_t = String::from("example.toml"))]
     config: String,
+
+    /// Maximal number of watched UTxOs kept in memory
+    #[arg(long, default_value_t = 100_000)]
+    utxo_cache_size: usize,
+
+    /// Recompute daily mean exchange ratios since the date (YYYY-MM-DD)
+    #[arg(long)]
+    backfill_mean_ratio_from: Option<String>,
+
+    /// Token registry snapshot file or directory of the registry JSON files
+    #[arg(long)]
+    token_registry: Option<String>,
+
+    /// Node-to-client socket of the node, enables the monitoring of the mempool
+    #[arg(long)]
+    mempool_socket: Option<String>,
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let args = Args::parse();
-    let config: config::Config = toml::from_str(&fs::read_to_string(&args.config)?)?;
+    let config: Arc<config::Config> = Arc::new(toml::from_str(&fs::read_to_string(&args.config)?)?);
     let fmt_layer = tracing_subscriber::fmt::layer();
     let filter = tracing_subscriber::filter::Targets::new()
         .with_target("oura", tracing::Level::WARN)
@@ -68,17 +99,64 @@ async fn main() -> anyhow::Result<()> {
 
     let db_path = args.database.clone();
     let addr: SocketAddr = args.bind.parse().unwrap();
+    let server_config = config.clone();
     let make_service = make_service_fn(move |_conn| {
         let db_path = db_path.clone();
-        let service = service_fn(move |req| server::route(req, db_path.clone()));
+        let config = server_config.clone();
+        let service = service_fn(move |req| server::route(req, db_path.clone(), config.clone()));
         async move { Ok::<_, Infallible>(service) }
     });
     let server = Server::bind(&addr).serve(make_service);
-    tokio::spawn(async move { server.await });
+    tokio::spawn(server);
 
-    let db = Database::connect(args.database).await?;
+    let db = match &args.database {
+        Some(database) => Some(Database::connect(database).await?),
+        None => None,
+    };
+    if args.persistent && db.is_none() {
+        return Err(anyhow::anyhow!("Persistent mode requires a database"));
+    }
+    // Without the persistent mode the database is only read
+    let store = db.as_ref().filter(|_| args.persistent);
+
+    if let Some(token_registry) = &args.token_registry {
+        registry::load(token_registry)?;
+        if let Some(db) = store {
+            queries::update_token_metadata(db).await?;
+        }
+    }
+
+    if let Some(db) = store {
+        let backfill_from = match &args.backfill_mean_ratio_from {
+            Some(date) => Some(aggregation::parse_date(date)?),
+            None => None,
+        };
+        tokio::spawn(aggregation::run_mean_exchange_ratio_job(
+            db.clone(),
+            backfill_from,
+        ));
+    }
+
+    if let Some(mempool_socket) = &args.mempool_socket {
+        tokio::spawn(mempool::run(
+            mempool_socket.clone(),
+            *setup::get_magic(),
+            config.clone(),
+            db.clone(),
+        ));
+    }
 
-    let (_handles, input) = setup::oura_bootstrap(args.start, args.socket)?;
-    sink::start(input, db, &config.pools, args.persistent).await?;
+    let well_known = setup::get_well_known_info()?;
+    let (_handles, input) = setup::oura_bootstrap(args.start, args.socket, well_known.clone())?;
+    sink::start(
+        input,
+        db,
+        args.persistent,
+        &config.pools,
+        &config.aggregation,
+        &well_known,
+        args.utxo_cache_size,
+    )
+    .await?;
     Ok(())
 }
@@ -0,0 +1,345 @@
+use std::{collections::HashSet, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use oura::{
+    mapper::{self, EventWriter},
+    model::TransactionRecord,
+    pipelining,
+};
+use pallas::{
+    codec::{minicbor, utils::Nullable},
+    ledger::{primitives::babbage::MintedTx, traverse::OriginalHash},
+};
+use sea_orm::DatabaseConnection;
+
+use crate::{
+    cache::UtxoCache,
+    config::{Config, PoolConfig},
+    orderbook, server,
+    sink::common,
+    types::{
+        BroadcastMessage, MempoolOrder, MempoolSettlement, OutputReference, PendingTransaction,
+        Swap,
+    },
+    utils,
+};
+
+pub mod n2c;
+
+// Delay before the first reconnect to the node, doubled up to the maximum after each failure.
+static RECONNECT_DELAY: Duration = Duration::from_secs(1);
+static MAX_RECONNECT_DELAY: Duration = Duration::from_secs(5 * 60);
+
+// Index of the Babbage era in the hard fork combinator, older transactions can't enter the
+// mempool anymore.
+static BABBAGE_ERA: u16 = 5;
+
+/// Source of the mempool snapshots, the LocalTxMonitor protocol of the node or a stand-in of it.
+#[async_trait]
+pub trait TxMonitor {
+    /// Acquire a snapshot of the mempool and return its slot. With a snapshot already acquired,
+    /// waits until the mempool changes.
+    async fn acquire(&mut self) -> anyhow::Result<u64>;
+    /// Next transaction of the snapshot as its era index and CBOR, `None` after the last one.
+    async fn next_tx(&mut self) -> anyhow::Result<Option<(u16, Vec<u8>)>>;
+}
+
+/// Publish the DEX transactions waiting in the mempool as pending websocket events, until they
+/// leave the mempool. The connection to the node is re-established with a growing delay when it
+/// fails.
+pub async fn run(
+    mempool_socket: String,
+    magic: u64,
+    config: Arc<Config>,
+    db: Option<DatabaseConnection>,
+) {
+    // Published transactions are kept over reconnects, so they are dropped once they leave
+    let mut pending: HashSet<String> = HashSet::new();
+    let mut delay = RECONNECT_DELAY;
+    loop {
+        let result = match n2c::NodeTxMonitor::connect(&mempool_socket, magic).await {
+            Ok(monitor) => {
+                delay = RECONNECT_DELAY;
+                monitor_mempool(monitor, &config, db.clone(), &mut pending).await
+            }
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            tracing::error!("Mempool monitor failed, reconnecting in {:?}: {}", delay, e);
+        }
+        tokio::time::sleep(delay).await;
+        delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+    }
+}
+
+async fn monitor_mempool(
+    mut monitor: impl TxMonitor + Send,
+    config: &Config,
+    db: Option<DatabaseConnection>,
+    pending: &mut HashSet<String>,
+) -> anyhow::Result<()> {
+    let pools: Vec<&PoolConfig> = config.pools.iter().filter(|p| p.enable).collect();
+    // Spent orders are resolved from the database, nothing is cached
+    let utxos = UtxoCache::new(&pools, 0, db);
+    // The records are built the same way as the ones of the chain
+    let (sender, _receiver) = pipelining::new_inter_stage_channel(None);
+    let writer = EventWriter::standalone(
+        sender,
+        None,
+        mapper::Config {
+            include_transaction_details: true,
+            ..Default::default()
+        },
+    );
+
+    loop {
+        let slot = monitor.acquire().await?;
+        let mut snapshot = HashSet::new();
+        while let Some((era, cbor)) = monitor.next_tx().await? {
+            if era != BABBAGE_ERA {
+                continue;
+            }
+            let transaction = match decode_transaction(&writer, &cbor) {
+                Ok(transaction) => transaction,
+                Err(e) => {
+                    tracing::warn!("Cannot decode mempool transaction: {}", e);
+                    continue;
+                }
+            };
+            snapshot.insert(transaction.hash.clone());
+            if pending.contains(&transaction.hash) {
+                continue;
+            }
+            match get_pending_transaction(&pools, &utxos, &transaction, slot).await {
+                Ok(Some(event)) => {
+                    server::ws_broadcast(&BroadcastMessage::Pending(event));
+                    pending.insert(transaction.hash);
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    tracing::warn!(
+                        "Cannot process mempool transaction {}: {}",
+                        transaction.hash,
+                        e
+                    )
+                }
+            }
+        }
+
+        for tx_hash in pending.difference(&snapshot) {
+            server::ws_broadcast(&BroadcastMessage::PendingDropped(tx_hash.clone()));
+        }
+        pending.retain(|tx_hash| snapshot.contains(tx_hash));
+    }
+}
+
+fn decode_transaction(writer: &EventWriter, cbor: &[u8]) -> anyhow::Result<TransactionRecord> {
+    let transaction: MintedTx = minicbor::decode(cbor)?;
+    let auxiliary_data = match &transaction.auxiliary_data {
+        Nullable::Some(auxiliary_data) => Some(auxiliary_data),
+        _ => None,
+    };
+    writer
+        .to_babbage_transaction_record(
+            &transaction.transaction_body,
+            &transaction.transaction_body.original_hash().to_string(),
+            auxiliary_data,
+            Some(&transaction.transaction_witness_set),
+        )
+        .map_err(|e| anyhow::anyhow!("{}", e))
+}
+
+/// Orders placed and pools settled by the transaction, `None` when it doesn't touch any pool.
+async fn get_pending_transaction(
+    pools: &[&PoolConfig],
+    utxos: &UtxoCache,
+    transaction: &TransactionRecord,
+    slot: u64,
+) -> anyhow::Result<Option<PendingTransaction>> {
+    let mut orders = Vec::new();
+    for (index, output) in transaction.outputs.iter().flatten().enumerate() {
+        let hash = utils::get_payment_hash(&output.address);
+        let pool = match pools
+            .iter()
+            .find(|p| hash == hex::decode(&p.request_hash).ok())
+        {
+            Some(pool) => pool,
+            None => continue,
+        };
+        let order = common::get_datum(transaction, output)
+            .map(|d| pool.as_trait().order(d))
+            .unwrap_or_default();
+        orders.push(MempoolOrder {
+            script_hash: pool.script_hash.clone(),
+            output: OutputReference {
+                tx_hash: transaction.hash.clone(),
+                index: index as u64,
+            },
+            offer: order
+                .buy
+                .as_ref()
+                .map(|buy| orderbook::get_offer(pool, output, buy)),
+            owner: order.owner,
+            buy: order.buy,
+            min_receive: order.min_receive,
+            deadline: order.deadline,
+        });
+    }
+
+    let mut settlements = Vec::new();
+    for pool in pools.iter() {
+        // The adapter reference isn't Send, so it must not be held over the await
+        let mean_value = pool.as_trait().mean_value(pool, transaction);
+        let (reserve1, reserve2) = match mean_value.await {
+            Some(reserves) => reserves,
+            None => continue,
+        };
+        let swaps = pool.as_trait().swaps(pool, utxos, transaction);
+        let swaps = swaps.await?;
+        // Pairs are reported in the canonical order, whatever order the DEX uses
+        let reversed = !utils::is_canonical_pair(&reserve1.asset, &reserve2.asset);
+        let (reserve1, reserve2) = match reversed {
+            true => (reserve2, reserve1),
+            false => (reserve1, reserve2),
+        };
+        settlements.push(MempoolSettlement {
+            script_hash: pool.script_hash.clone(),
+            reserve1,
+            reserve2,
+            swaps: swaps
+                .into_iter()
+                .map(|s| if reversed { s.reversed() } else { s })
+                .collect::<Vec<Swap>>(),
+        });
+    }
+
+    if orders.is_empty() && settlements.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(PendingTransaction {
+        tx_hash: transaction.hash.clone(),
+        slot,
+        orders,
+        settlements,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use pallas::codec::minicbor::Encoder;
+    use tokio::sync::broadcast;
+
+    use super::*;
+
+    static CONFIG: &str = r#"
+        [[pools]]
+        script_hash = "e1317b152faac13426e6a83e06ff88a4d62cce3c1634ab0a5ec13309"
+        request_hash = "a65ca58a4e9c755fa830173d2a5caed458ac0c73f97db7faae2e7e3b"
+        vesting_hash = "73c3f85a23b2b81d7df84a9616b666b1e1c8c5fcfff6783d9c4a1a45"
+        address = "addr1wxn9efv2f6w82hagxqtn62ju4m293tqvw0uhmdl64ch8uwc0h43gt"
+        type = "MinSwapV1"
+    "#;
+
+    /// Stand-in of the node replaying prepared snapshots of the mempool.
+    struct SnapshotMonitor {
+        snapshots: VecDeque<Vec<Vec<u8>>>,
+        current: Vec<Vec<u8>>,
+        slot: u64,
+    }
+
+    #[async_trait]
+    impl TxMonitor for SnapshotMonitor {
+        async fn acquire(&mut self) -> anyhow::Result<u64> {
+            self.current = self
+                .snapshots
+                .pop_front()
+                .ok_or_else(|| anyhow::anyhow!("No more snapshots"))?;
+            self.slot += 1;
+            Ok(self.slot)
+        }
+
+        async fn next_tx(&mut self) -> anyhow::Result<Option<(u16, Vec<u8>)>> {
+            Ok(self.current.pop().map(|cbor| (BABBAGE_ERA, cbor)))
+        }
+    }
+
+    // Babbage transaction sending ADA to a mainnet script address without a stake part.
+    fn get_transaction_cbor(script_hash: &str) -> Vec<u8> {
+        let mut address = vec![0x71];
+        address.extend(hex::decode(script_hash).unwrap());
+        let mut encoder = Encoder::new(Vec::new());
+        encoder
+            .array(4)
+            .unwrap()
+            .map(3)
+            .unwrap()
+            .u8(0)
+            .unwrap()
+            .array(1)
+            .unwrap()
+            .array(2)
+            .unwrap()
+            .bytes(&[1; 32])
+            .unwrap()
+            .u8(0)
+            .unwrap()
+            .u8(1)
+            .unwrap()
+            .array(1)
+            .unwrap()
+            .array(2)
+            .unwrap()
+            .bytes(&address)
+            .unwrap()
+            .u64(4_000_000)
+            .unwrap()
+            .u8(2)
+            .unwrap()
+            .u64(200_000)
+            .unwrap()
+            .map(0)
+            .unwrap()
+            .bool(true)
+            .unwrap()
+            .null()
+            .unwrap();
+        encoder.into_writer()
+    }
+
+    #[tokio::test]
+    async fn publishes_pending_orders_until_dropped() {
+        let (sender, mut receiver) = broadcast::channel(16);
+        *server::WS_BROADCAST_CHANNEL.write().unwrap() = Some(sender);
+        let config: Config = toml::from_str(CONFIG).unwrap();
+        let order = get_transaction_cbor(&config.pools[0].request_hash);
+        let monitor = SnapshotMonitor {
+            snapshots: VecDeque::from([vec![order.clone()], vec![order], vec![]]),
+            current: Vec::new(),
+            slot: 0,
+        };
+
+        // The monitor fails once the snapshots run out
+        let mut pending = HashSet::new();
+        assert!(monitor_mempool(monitor, &config, None, &mut pending)
+            .await
+            .is_err());
+
+        let message: serde_json::Value =
+            serde_json::from_str(&receiver.try_recv().unwrap()).unwrap();
+        assert_eq!(message["operation"], "Pending");
+        assert_eq!(message["data"]["slot"], 1);
+        let orders = message["data"]["orders"].as_array().unwrap();
+        assert_eq!(orders.len(), 1);
+        assert_eq!(orders[0]["script_hash"], config.pools[0].script_hash);
+        let tx_hash = message["data"]["tx_hash"].clone();
+
+        // Not published again while it stays in the mempool, dropped once it leaves
+        let message: serde_json::Value =
+            serde_json::from_str(&receiver.try_recv().unwrap()).unwrap();
+        assert_eq!(message["operation"], "PendingDropped");
+        assert_eq!(message["data"], tx_hash);
+        assert!(receiver.try_recv().is_err());
+    }
+}
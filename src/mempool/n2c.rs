@@ -0,0 +1,150 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use pallas::codec::minicbor::{decode, Decoder, Encoder};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::UnixStream,
+};
+
+use super::TxMonitor;
+
+static HANDSHAKE_PROTOCOL: u16 = 0;
+static TX_MONITOR_PROTOCOL: u16 = 9;
+
+// Segments sent by the node have the mode bit set.
+static RESPONDER_MODE: u16 = 0x8000;
+
+// Node-to-client versions 12 to 14, LocalTxMonitor came with the version 12. The versions are
+// distinguished from the node-to-node ones by the bit 15.
+static VERSIONS: [u64; 3] = [32_780, 32_781, 32_782];
+
+/// LocalTxMonitor client of the node-to-client protocol over the node socket.
+pub struct NodeTxMonitor {
+    stream: UnixStream,
+    // Payload of the received segments not decoded yet
+    buffer: Vec<u8>,
+}
+
+impl NodeTxMonitor {
+    /// Connect to the node socket and negotiate the protocol version.
+    pub async fn connect(path: &str, magic: u64) -> anyhow::Result<Self> {
+        let mut monitor = NodeTxMonitor {
+            stream: UnixStream::connect(path).await?,
+            buffer: Vec::new(),
+        };
+
+        // MsgProposeVersions [0, {version => network magic}]
+        let mut encoder = Encoder::new(Vec::new());
+        encoder.array(2)?.u8(0)?.map(VERSIONS.len() as u64)?;
+        for version in VERSIONS.iter() {
+            encoder.u64(*version)?.u64(magic)?;
+        }
+        monitor
+            .send(HANDSHAKE_PROTOCOL, encoder.into_writer())
+            .await?;
+
+        // MsgAcceptVersion [1, version, data] or MsgRefuse [2, reason]
+        let version = monitor
+            .receive(HANDSHAKE_PROTOCOL, |d| {
+                d.array()?;
+                match d.u8()? {
+                    1 => {
+                        let version = d.u64()?;
+                        d.skip()?;
+                        Ok(Some(version))
+                    }
+                    _ => {
+                        d.skip()?;
+                        Ok(None)
+                    }
+                }
+            })
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Node refused the LocalTxMonitor versions"))?;
+        tracing::info!("Mempool monitor connected, version {}", version & 0x7fff);
+        Ok(monitor)
+    }
+
+    async fn send(&mut self, protocol: u16, payload: Vec<u8>) -> anyhow::Result<()> {
+        // The segment header is the sender time in microseconds, the protocol and the length
+        let time = SystemTime::now().duration_since(UNIX_EPOCH)?.as_micros() as u32;
+        let mut segment = Vec::with_capacity(payload.len() + 8);
+        segment.extend(time.to_be_bytes());
+        segment.extend(protocol.to_be_bytes());
+        segment.extend((payload.len() as u16).to_be_bytes());
+        segment.extend(payload);
+        self.stream.write_all(&segment).await?;
+        Ok(())
+    }
+
+    // Read segments of the protocol until a whole message is decoded, messages can span
+    // several segments.
+    async fn receive<T>(
+        &mut self,
+        protocol: u16,
+        parse: impl Fn(&mut Decoder) -> Result<T, decode::Error>,
+    ) -> anyhow::Result<T> {
+        loop {
+            if !self.buffer.is_empty() {
+                let mut decoder = Decoder::new(&self.buffer);
+                match parse(&mut decoder) {
+                    Ok(message) => {
+                        let position = decoder.position();
+                        self.buffer.drain(..position);
+                        return Ok(message);
+                    }
+                    Err(e) if e.is_end_of_input() => (),
+                    Err(e) => return Err(e.into()),
+                }
+            }
+
+            let mut header = [0u8; 8];
+            self.stream.read_exact(&mut header).await?;
+            let segment_protocol = u16::from_be_bytes([header[4], header[5]]) & !RESPONDER_MODE;
+            let length = u16::from_be_bytes([header[6], header[7]]) as usize;
+            let mut payload = vec![0u8; length];
+            self.stream.read_exact(&mut payload).await?;
+            if segment_protocol == protocol {
+                self.buffer.extend(payload);
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl TxMonitor for NodeTxMonitor {
+    async fn acquire(&mut self) -> anyhow::Result<u64> {
+        // MsgAcquire [1], MsgAcquired [2, slot]
+        let mut encoder = Encoder::new(Vec::new());
+        encoder.array(1)?.u8(1)?;
+        self.send(TX_MONITOR_PROTOCOL, encoder.into_writer())
+            .await?;
+        self.receive(TX_MONITOR_PROTOCOL, |d| {
+            d.array()?;
+            d.u8()?;
+            d.u64()
+        })
+        .await
+    }
+
+    async fn next_tx(&mut self) -> anyhow::Result<Option<(u16, Vec<u8>)>> {
+        // MsgNextTx [5], MsgReplyNextTx [6] or [6, [era, #6.24(transaction CBOR)]]
+        let mut encoder = Encoder::new(Vec::new());
+        encoder.array(1)?.u8(5)?;
+        self.send(TX_MONITOR_PROTOCOL, encoder.into_writer())
+            .await?;
+        self.receive(TX_MONITOR_PROTOCOL, |d| {
+            let length = d.array()?;
+            d.u8()?;
+            if length != Some(2) {
+                return Ok(None);
+            }
+            d.array()?;
+            let era = d.u16()?;
+            d.tag()?;
+            Ok(Some((era, d.bytes()?.to_vec())))
+        })
+        .await
+    }
+}
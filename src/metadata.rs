@@ -0,0 +1,136 @@
+use oura::model::{MetadatumRendition, TransactionRecord};
+
+use crate::types::{Asset, TokenMetadata};
+
+// CIP-67 asset name prefixes of the CIP-68 token classes.
+static CIP68_REFERENCE_PREFIX: &str = "000643b0";
+static CIP68_NFT_PREFIX: &str = "000de140";
+static CIP68_FT_PREFIX: &str = "0014df10";
+
+// Transaction metadata label of the CIP-25 NFT metadata.
+static CIP25_LABEL: &str = "721";
+
+/// Token metadata published by the transaction, either in CIP-68 reference token datums or in
+/// CIP-25 mint metadata. CIP-68 metadata is returned last, so it takes precedence when a token
+/// is described both ways.
+pub fn get_token_metadata(transaction: &TransactionRecord) -> Vec<(Asset, TokenMetadata)> {
+    let mut result = get_cip25_metadata(transaction);
+    result.extend(get_cip68_metadata(transaction));
+    result
+}
+
+fn get_cip25_metadata(transaction: &TransactionRecord) -> Vec<(Asset, TokenMetadata)> {
+    let mut result = Vec::new();
+    for record in transaction.metadata.iter().flatten() {
+        let map = match &record.content {
+            MetadatumRendition::MapJson(map) if record.label == CIP25_LABEL => map,
+            _ => continue,
+        };
+        // Version 1 uses text asset names, version 2 raw bytes rendered as hex
+        let version = map["version"].as_i64().unwrap_or(1);
+        for (policy_id, assets) in map.as_object().into_iter().flatten() {
+            if policy_id == "version" {
+                continue;
+            }
+            for (name, fields) in assets.as_object().into_iter().flatten() {
+                let name = match version {
+                    1 => hex::encode(name),
+                    _ => name.to_lowercase(),
+                };
+                let metadata = TokenMetadata {
+                    decimals: fields["decimals"].as_i64().map(|d| d as i32),
+                    ticker: get_cip25_string(&fields["ticker"]),
+                    display_name: get_cip25_string(&fields["name"]),
+                    logo: get_cip25_string(&fields["logo"]),
+                };
+                if metadata != TokenMetadata::default() {
+                    result.push((
+                        Asset {
+                            policy_id: policy_id.to_lowercase(),
+                            name,
+                        },
+                        metadata,
+                    ));
+                }
+            }
+        }
+    }
+    result
+}
+
+// Strings longer than 64 bytes are split into a list of chunks in the metadata.
+fn get_cip25_string(value: &serde_json::Value) -> Option<String> {
+    match value {
+        serde_json::Value::String(s) => Some(s.clone()),
+        serde_json::Value::Array(chunks) => chunks
+            .iter()
+            .map(|c| c.as_str())
+            .collect::<Option<Vec<&str>>>()
+            .map(|c| c.concat()),
+        _ => None,
+    }
+}
+
+fn get_cip68_metadata(transaction: &TransactionRecord) -> Vec<(Asset, TokenMetadata)> {
+    let mut result = Vec::new();
+    for output in transaction.outputs.iter().flatten() {
+        let datum = match &output.inline_datum {
+            Some(datum) => &datum.plutus_data,
+            None => continue,
+        };
+        for asset in output.assets.iter().flatten() {
+            let name = match asset.asset.strip_prefix(CIP68_REFERENCE_PREFIX) {
+                Some(name) => name,
+                None => continue,
+            };
+            // The datum is Constr 0 [metadata, version, extra]
+            let metadata = match datum["fields"][0]["map"].as_array() {
+                Some(fields) => get_cip68_fields(fields),
+                None => continue,
+            };
+            // The reference token describes both the NFT and the fungible token of the name
+            for prefix in [CIP68_NFT_PREFIX, CIP68_FT_PREFIX] {
+                result.push((
+                    Asset {
+                        policy_id: asset.policy.clone(),
+                        name: format!("{}{}", prefix, name),
+                    },
+                    metadata.clone(),
+                ));
+            }
+        }
+    }
+    result
+}
+
+fn get_cip68_fields(fields: &[serde_json::Value]) -> TokenMetadata {
+    let mut metadata = TokenMetadata::default();
+    for field in fields.iter() {
+        let key = field["k"]["bytes"]
+            .as_str()
+            .and_then(|k| hex::decode(k).ok())
+            .and_then(|k| String::from_utf8(k).ok());
+        let value = &field["v"];
+        match key.as_deref() {
+            Some("decimals") => metadata.decimals = value["int"].as_i64().map(|d| d as i32),
+            Some("ticker") => metadata.ticker = get_cip68_string(value),
+            Some("name") => metadata.display_name = get_cip68_string(value),
+            Some("logo") => metadata.logo = get_cip68_string(value),
+            _ => (),
+        }
+    }
+    metadata
+}
+
+// Text is stored as UTF-8 bytes, possibly split into a list of chunks.
+fn get_cip68_string(value: &serde_json::Value) -> Option<String> {
+    let bytes = match value["list"].as_array() {
+        Some(chunks) => chunks
+            .iter()
+            .map(|c| c["bytes"].as_str().and_then(|b| hex::decode(b).ok()))
+            .collect::<Option<Vec<Vec<u8>>>>()?
+            .concat(),
+        None => hex::decode(value["bytes"].as_str()?).ok()?,
+    };
+    String::from_utf8(bytes).ok()
+}
@@ -0,0 +1,188 @@
+use std::collections::HashMap;
+
+use sea_orm::DatabaseConnection;
+
+use crate::{
+    queries,
+    types::{AlertKind, MevAlert, OutputReference, SwapInfo},
+    utils,
+};
+
+/// Swap decoded in the current block, in the order of the execution.
+pub struct BlockSwap {
+    pub swap: SwapInfo,
+    // Stored transaction of the swap, None without a database
+    pub tx_id: Option<i64>,
+    // Hex encoded key hash of the batcher which executed the swap
+    pub batcher: Option<String>,
+}
+
+// Swaps of a pool in the block, keyed by the script hash and the pair.
+type PoolSwaps<'a> = ((&'a String, i64, i64), Vec<&'a BlockSwap>);
+
+// Wallet of the trader, the stake credential groups the addresses of a wallet.
+fn get_wallet(swap: &SwapInfo) -> Option<&String> {
+    swap.stake_credential
+        .as_ref()
+        .or(swap.payment_credential.as_ref())
+}
+
+// Sequences of a single pool where a wallet trades in the direction of the following swaps of
+// other traders and then trades back. Returns the positions of the front run, the victims and
+// the back run.
+fn find_sandwiches(swaps: &[&BlockSwap]) -> Vec<Vec<usize>> {
+    let mut sequences = Vec::new();
+    let mut front = 0;
+    while front < swaps.len() {
+        let attacker = get_wallet(&swaps[front].swap);
+        let direction = &swaps[front].swap.direction;
+        let back = attacker.and_then(|attacker| {
+            (front + 1..swaps.len()).find(|i| {
+                get_wallet(&swaps[*i].swap) == Some(attacker)
+                    && swaps[*i].swap.direction != *direction
+            })
+        });
+        let back = match back {
+            Some(back) => back,
+            None => {
+                front += 1;
+                continue;
+            }
+        };
+        let victims: Vec<usize> = (front + 1..back)
+            .filter(|i| {
+                get_wallet(&swaps[*i].swap) != attacker && swaps[*i].swap.direction == *direction
+            })
+            .collect();
+        if victims.is_empty() {
+            front += 1;
+            continue;
+        }
+        let mut sequence = vec![front];
+        sequence.extend(victims);
+        sequence.push(back);
+        sequences.push(sequence);
+        front = back + 1;
+    }
+    sequences
+}
+
+// Orders of the batcher executed before the orders of the same batch placed earlier. Returns
+// the positions of the batcher order and of the skipped orders.
+fn find_front_running(
+    swaps: &[&BlockSwap],
+    placements: &HashMap<(String, u64), (i64, i64)>,
+) -> Vec<Vec<usize>> {
+    let placement = |swap: &SwapInfo| {
+        let order = swap.origin.output.as_ref()?;
+        placements
+            .get(&(order.tx_hash.clone(), order.index))
+            .copied()
+    };
+    let mut sequences = Vec::new();
+    for (position, block_swap) in swaps.iter().enumerate() {
+        let batcher = match &block_swap.batcher {
+            Some(batcher) => batcher,
+            None => continue,
+        };
+        if block_swap.swap.payment_credential.as_ref() != Some(batcher) {
+            continue;
+        }
+        let placed = match placement(&block_swap.swap) {
+            Some(placed) => placed,
+            None => continue,
+        };
+        let skipped: Vec<usize> = (position + 1..swaps.len())
+            .filter(|i| swaps[*i].swap.origin.tx_hash == block_swap.swap.origin.tx_hash)
+            .filter(|i| placement(&swaps[*i].swap).filter(|p| *p < placed).is_some())
+            .collect();
+        if !skipped.is_empty() {
+            let mut sequence = vec![position];
+            sequence.extend(skipped);
+            sequences.push(sequence);
+        }
+    }
+    sequences
+}
+
+// The sequence starts with the swap of the attacker.
+fn get_alert(kind: AlertKind, swaps: &[&BlockSwap], sequence: &[usize]) -> (Option<i64>, MevAlert) {
+    let first = swaps[sequence[0]];
+    let last = swaps[*sequence.last().unwrap()];
+    let attacker = match kind {
+        AlertKind::Sandwich => get_wallet(&first.swap).cloned(),
+        AlertKind::BatcherFrontRunning => first.batcher.clone(),
+    };
+    let mut victims: Vec<String> = sequence
+        .iter()
+        .filter_map(|i| get_wallet(&swaps[*i].swap))
+        .filter(|w| Some(*w) != attacker.as_ref())
+        .cloned()
+        .collect();
+    victims.sort();
+    victims.dedup();
+    let event_ids: Vec<String> = sequence
+        .iter()
+        .map(|i| swaps[*i].swap.origin.event_id.clone())
+        .collect();
+    let alert = MevAlert {
+        event_id: utils::get_alert_event_id(kind.name(), &event_ids),
+        kind: kind.name().to_string(),
+        script_hash: first.swap.script_hash.clone(),
+        asset1: first.swap.asset1,
+        asset2: first.swap.asset2,
+        attacker,
+        victims,
+        swaps: event_ids,
+        tx_hash: last.swap.origin.tx_hash.clone(),
+        block_hash: last.swap.origin.block_hash.clone(),
+        slot: last.swap.origin.slot,
+        timestamp: last.swap.timestamp,
+    };
+    (last.tx_id, alert)
+}
+
+/// Suspicious sequences among the swaps of the block, together with the stored transaction of
+/// the last swap of each. Front running by the batchers needs the order placements, so it's
+/// only detected with a database.
+pub async fn detect(
+    swaps: &[BlockSwap],
+    db: Option<&DatabaseConnection>,
+) -> anyhow::Result<Vec<(Option<i64>, MevAlert)>> {
+    let placements = match db {
+        Some(db) => {
+            let orders: Vec<OutputReference> = swaps
+                .iter()
+                .filter(|s| s.batcher.is_some())
+                .filter_map(|s| s.swap.origin.output.clone())
+                .collect();
+            queries::get_order_placements(&orders, db).await?
+        }
+        None => HashMap::new(),
+    };
+
+    let mut pools: Vec<PoolSwaps> = Vec::new();
+    for block_swap in swaps.iter() {
+        let swap = &block_swap.swap;
+        let key = (&swap.script_hash, swap.asset1, swap.asset2);
+        match pools.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, pool_swaps)) => pool_swaps.push(block_swap),
+            None => pools.push((key, vec![block_swap])),
+        }
+    }
+
+    let mut alerts = Vec::new();
+    for (_, pool_swaps) in pools.iter() {
+        for sequence in find_sandwiches(pool_swaps) {
+            alerts.push(get_alert(AlertKind::Sandwich, pool_swaps, &sequence));
+        }
+        for sequence in find_front_running(pool_swaps, &placements) {
+            alerts.push(get_alert(
+                AlertKind::BatcherFrontRunning,
+                pool_swaps,
+                &sequence,
+            ));
+        }
+    }
+    Ok(alerts)
+}
@@ -0,0 +1,294 @@
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, VecDeque},
+    sync::RwLock,
+};
+
+use oura::model::{TransactionRecord, TxInputRecord, TxOutputRecord};
+use rust_decimal::Decimal;
+use sea_orm::DatabaseConnection;
+
+use crate::{
+    cache::{TokenRegistry, ROLLBACK_DEPTH},
+    config::PoolConfig,
+    queries,
+    sink::common::{self, OrderDatum},
+    types::{Asset, AssetAmount, DepthLevel, OrderBookDepth, OrderStatus},
+    utils,
+};
+
+// Pool identification: the script hash and the token pair in the canonical order.
+pub type PoolKey = (String, i64, i64);
+
+type OutputRef = (String, u64);
+
+// Significant digits of the price levels.
+static DEFAULT_PRECISION: u32 = 3;
+
+/// Swap order sitting at the request address, waiting for a batcher.
+struct PendingOrder {
+    pool: PoolKey,
+    // false - the first token is sold for the second one
+    direction: bool,
+    // Offered amount of the sold token, batcher fee and deposit excluded
+    amount: u64,
+    min_receive: Option<u64>,
+    deadline: Option<i64>,
+}
+
+// Orders placed and spent in a block together with the time of the previous block.
+struct BlockChanges {
+    slot: u64,
+    timestamp: i64,
+    placed: Vec<OutputRef>,
+    spent: Vec<(OutputRef, PendingOrder)>,
+}
+
+#[derive(Default)]
+struct OrderBook {
+    orders: HashMap<OutputRef, PendingOrder>,
+    // Time of the latest block, orders past their deadline are left out of the depth
+    timestamp: i64,
+    // Changes of the last `ROLLBACK_DEPTH` blocks, so rollbacks can be reverted without a database
+    changes: VecDeque<BlockChanges>,
+}
+
+// Unspent swap orders of all pools.
+static ORDER_BOOK: RwLock<Option<OrderBook>> = RwLock::new(None);
+
+/// Replace the order book with the open orders stored in the database. Used on startup and
+/// after rollbacks.
+pub async fn load(
+    pools: &[&PoolConfig],
+    tokens: &mut TokenRegistry,
+    db: &DatabaseConnection,
+) -> anyhow::Result<()> {
+    *ORDER_BOOK.write().unwrap() = Some(OrderBook {
+        orders: HashMap::new(),
+        timestamp: queries::get_last_block_timestamp(db)
+            .await?
+            .unwrap_or_default(),
+        changes: VecDeque::new(),
+    });
+
+    let orders = queries::get_orders(None, OrderStatus::Open, i64::MAX as u64, db).await?;
+    let inputs: Vec<TxInputRecord> = orders
+        .iter()
+        .map(|o| TxInputRecord {
+            tx_id: o.output.tx_hash.clone(),
+            index: o.output.index,
+        })
+        .collect();
+    let outputs = queries::get_utxo_input(&inputs, db).await?;
+    for (order, output) in orders.iter().zip(outputs.iter()) {
+        let (datum, output) = match (&order.datum, output) {
+            (Some(datum), Some(output)) => (datum, output),
+            _ => continue,
+        };
+        let pool = match pools.iter().find(|p| p.script_hash == order.script_hash) {
+            Some(pool) => pool,
+            None => continue,
+        };
+        let output_ref = (order.output.tx_hash.clone(), order.output.index);
+        let decoded = pool.as_trait().order(datum);
+        place(pool, output_ref, output, &decoded, tokens).await;
+    }
+    Ok(())
+}
+
+pub fn start_block(slot: u64, timestamp: i64) {
+    let mut order_book = ORDER_BOOK.write().unwrap();
+    let order_book = order_book.get_or_insert_with(OrderBook::default);
+    order_book.changes.push_back(BlockChanges {
+        slot,
+        timestamp: order_book.timestamp,
+        placed: Vec::new(),
+        spent: Vec::new(),
+    });
+    while order_book.changes.len() > ROLLBACK_DEPTH {
+        order_book.changes.pop_front();
+    }
+    order_book.timestamp = timestamp;
+}
+
+/// Revert the orders placed and spent in all blocks after the given slot.
+pub fn rollback_to_slot(slot: u64) {
+    let mut order_book = ORDER_BOOK.write().unwrap();
+    let order_book = match order_book.as_mut() {
+        Some(order_book) => order_book,
+        None => return,
+    };
+    while matches!(order_book.changes.back(), Some(block) if block.slot > slot) {
+        // Spent orders are restored first, as an order can be placed and spent in one block
+        let block = order_book.changes.pop_back().unwrap();
+        order_book.orders.extend(block.spent);
+        for output_ref in block.placed.iter() {
+            order_book.orders.remove(output_ref);
+        }
+        order_book.timestamp = block.timestamp;
+    }
+}
+
+/// Sold token and the offered amount of the swap order at the output, batcher fee and deposit
+/// excluded.
+pub fn get_offer(pool: &PoolConfig, output: &TxOutputRecord, buy: &Asset) -> AssetAmount {
+    // The order holds the sold token, only ADA when ADA is sold
+    let sell = output
+        .assets
+        .iter()
+        .flatten()
+        .map(|a| Asset {
+            policy_id: a.policy.clone(),
+            name: a.asset.clone(),
+        })
+        .find(|a| a.policy_id != buy.policy_id || a.name != buy.name)
+        .unwrap_or(Asset {
+            policy_id: String::new(),
+            name: String::new(),
+        });
+    let amount = common::get_amount(output, &sell.policy_id, &sell.name).saturating_sub(
+        common::reduce_ada_amount(
+            &sell.policy_id,
+            &sell.name,
+            pool.as_trait().fees().ada_swap_in,
+        ),
+    );
+    AssetAmount {
+        asset: sell,
+        amount,
+    }
+}
+
+/// Add the swap order placed at the output. Returns the pool of the order, `None` when the order
+/// isn't a swap or its tokens are unknown.
+pub async fn place(
+    pool: &PoolConfig,
+    output_ref: OutputRef,
+    output: &TxOutputRecord,
+    order: &OrderDatum,
+    tokens: &mut TokenRegistry,
+) -> Option<PoolKey> {
+    let buy = order.buy.as_ref()?;
+    let AssetAmount {
+        asset: sell,
+        amount,
+    } = get_offer(pool, output, buy);
+
+    // Orders of the tokens without a known pool can't be executed anyway
+    let sell_id = tokens.get_id(&sell).await.ok()?;
+    let buy_id = tokens.get_id(buy).await.ok()?;
+    let (key, direction) = match utils::is_canonical_pair(&sell, buy) {
+        true => ((pool.script_hash.clone(), sell_id, buy_id), false),
+        false => ((pool.script_hash.clone(), buy_id, sell_id), true),
+    };
+
+    let mut order_book = ORDER_BOOK.write().unwrap();
+    let order_book = order_book.get_or_insert_with(OrderBook::default);
+    if let Some(block) = order_book.changes.back_mut() {
+        block.placed.push(output_ref.clone());
+    }
+    order_book.orders.insert(
+        output_ref,
+        PendingOrder {
+            pool: key.clone(),
+            direction,
+            amount,
+            min_receive: order.min_receive,
+            deadline: order.deadline,
+        },
+    );
+    Some(key)
+}
+
+/// Remove the orders spent by the transaction. Returns the pools of the removed orders.
+pub fn spend(transaction: &TransactionRecord) -> Vec<PoolKey> {
+    let mut order_book = ORDER_BOOK.write().unwrap();
+    let order_book = match order_book.as_mut() {
+        Some(order_book) => order_book,
+        None => return Vec::new(),
+    };
+    let mut pools = Vec::new();
+    for input in transaction.inputs.iter().flatten() {
+        let output_ref = (input.tx_id.clone(), input.index);
+        if let Some(order) = order_book.orders.remove(&output_ref) {
+            pools.push(order.pool.clone());
+            if let Some(block) = order_book.changes.back_mut() {
+                block.spent.push((output_ref, order));
+            }
+        }
+    }
+    pools
+}
+
+fn get_levels<'a>(
+    orders: impl Iterator<Item = &'a PendingOrder>,
+    precision: u32,
+) -> Vec<DepthLevel> {
+    let mut levels: HashMap<Option<Decimal>, DepthLevel> = HashMap::new();
+    for order in orders {
+        // Limit price of the second token in units of the first one
+        let price = order
+            .min_receive
+            .and_then(|min_receive| match order.direction {
+                false => utils::get_rate(order.amount, min_receive),
+                true => utils::get_rate(min_receive, order.amount),
+            })
+            .and_then(|price| price.round_sf(precision));
+        let level = levels.entry(price).or_insert(DepthLevel {
+            price,
+            amount: 0,
+            orders: 0,
+        });
+        level.amount += order.amount;
+        level.orders += 1;
+    }
+    levels.into_values().collect()
+}
+
+/// Unfilled swap orders of the pool grouped by the limit price rounded to `precision`
+/// significant digits. Market orders without a minimum receive come first.
+pub fn get_depth(key: &PoolKey, precision: Option<u32>) -> OrderBookDepth {
+    let precision = precision.unwrap_or(DEFAULT_PRECISION);
+    let order_book = ORDER_BOOK.read().unwrap();
+    let timestamp = order_book.as_ref().map(|b| b.timestamp).unwrap_or_default();
+    let orders: Vec<&PendingOrder> = order_book
+        .iter()
+        .flat_map(|b| b.orders.values())
+        .filter(|o| o.pool == *key && o.deadline.filter(|d| *d < timestamp).is_none())
+        .collect();
+
+    let mut bids = get_levels(orders.iter().copied().filter(|o| !o.direction), precision);
+    bids.sort_by_key(|l| l.price.map(Reverse));
+    let mut asks = get_levels(orders.iter().copied().filter(|o| o.direction), precision);
+    asks.sort_by_key(|l| l.price);
+    OrderBookDepth {
+        script_hash: key.0.clone(),
+        asset1: key.1,
+        asset2: key.2,
+        bids,
+        asks,
+        timestamp,
+    }
+}
+
+/// Depth of every pool of the pair with pending orders, optionally of a single pool only. The
+/// tokens can be given in either order, the depth is reported for the canonical one.
+pub fn get_depths(
+    asset1: i64,
+    asset2: i64,
+    script_hash: Option<&str>,
+    precision: Option<u32>,
+) -> Vec<OrderBookDepth> {
+    let mut keys: Vec<PoolKey> = ORDER_BOOK
+        .read()
+        .unwrap()
+        .iter()
+        .flat_map(|b| b.orders.values())
+        .map(|o| o.pool.clone())
+        .filter(|(_, a1, a2)| (*a1, *a2) == (asset1, asset2) || (*a1, *a2) == (asset2, asset1))
+        .filter(|(s, _, _)| script_hash.is_none() || script_hash == Some(s.as_str()))
+        .collect();
+    keys.sort();
+    keys.dedup();
+    keys.iter().map(|k| get_depth(k, precision)).collect()
+}
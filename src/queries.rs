@@ -4,45 +4,105 @@ use std::{
 };
 
 use crate::{
+    candles::{Interval, SeriesKey},
     entity::{
-        address, block, price_update, swap, token, token_transfer, transaction, transaction_output,
+        address, arbitrage_opportunity, batch, block, candle, mean_exchange_ratio, mev_alert,
+        order, price_update, swap, token, token_transfer, transaction, transaction_output,
     },
-    types::{Asset, ExchangeHistory, ExchangeRate, SwapHistory, SwapInfo},
-    utils::ADA_TOKEN,
+    registry,
+    sink::common::OrderDatum,
+    types::{
+        AlertKind, ArbitrageOpportunity, Asset, AssetAmount, AssetInfo, BatchInfo, BatcherStats,
+        Candle, EventOrigin, ExchangeHistory, ExchangeRate, MeanExchangeRatio, MevAlert, OrderInfo,
+        OrderStatus, OutputReference, PoolState, PoolVolume, SwapHistory, SwapInfo, TokenMetadata,
+        TraderSwap,
+    },
+    utils::{self, ADA_TOKEN},
 };
 use oura::model::{
     BlockRecord, OutputAssetRecord, TransactionRecord, TxInputRecord, TxOutputRecord,
 };
 use sea_orm::{
-    ActiveModelTrait, ColumnTrait, Condition, DatabaseConnection, DbBackend, EntityTrait,
-    FromQueryResult, JoinType, Order, QueryFilter, QueryOrder, QuerySelect, RelationTrait, Set,
-    Statement,
+    prelude::{DateTime, Decimal},
+    sea_query::Expr,
+    ActiveModelBehavior, ActiveModelTrait, ColumnTrait, Condition, ConnectionTrait,
+    DatabaseConnection, DbBackend, EntityTrait, FromQueryResult, IntoActiveModel, JoinType, NotSet,
+    Order, QueryFilter, QueryOrder, QuerySelect, RelationTrait, Set, Statement,
 };
 
-pub async fn insert_block(block: &BlockRecord, db: &DatabaseConnection) -> anyhow::Result<i64> {
+/// Insert the block, or update it when a block with the same hash is already stored, so that
+/// overlapping ranges of the chain can be processed again.
+pub async fn insert_block(
+    block: &BlockRecord,
+    timestamp: i64,
+    db: &DatabaseConnection,
+) -> anyhow::Result<i64> {
+    let hash = hex::decode(block.hash.clone())?;
     let previous_hash = hex::decode(block.previous_hash.clone())?;
     let previous_block_model = block::Entity::find()
         .filter(block::Column::Hash.eq(previous_hash))
         .one(db)
         .await?;
+    let existing_block_model = block::Entity::find()
+        .filter(block::Column::Hash.eq(hash.clone()))
+        .one(db)
+        .await?;
     let block_model = block::ActiveModel {
-        hash: Set(hex::decode(block.hash.clone())?),
+        id: match &existing_block_model {
+            Some(b) => Set(b.id),
+            None => NotSet,
+        },
+        hash: Set(hash),
         height: Set(block.number as i64),
         epoch: Set(block
             .epoch
             .ok_or_else(|| anyhow::anyhow!("No block epoch"))? as i64),
         slot: Set(block.slot as i64),
         previous_block_id: Set(previous_block_model.map(|b| b.id)),
-        ..Default::default()
+        timestamp: Set(DateTime::from_timestamp_opt(timestamp, 0)),
+    };
+    let block_model = match existing_block_model {
+        Some(_) => block_model.update(db).await?,
+        None => block_model.insert(db).await?,
     };
-    let block_model = block_model.insert(db).await?;
     Ok(block_model.id)
 }
 
+// Number of rows looked up by a single query, keeps the bound parameters within the limits of
+// both Postgres and SQLite.
+const QUERY_CHUNK_SIZE: usize = 1000;
+
+#[derive(FromQueryResult)]
+struct QueryIdResult {
+    id: i64,
+}
+
 pub async fn rollback_to_slot(slot: &u64, db: &DatabaseConnection) -> anyhow::Result<()> {
     // We remove all blocks that are after the given slot. Removing based on the rollback event's
     // block_hash might not work because it's affected by the --start option and thus the
     // corresponding block might not even be present in the db.
+    let removed = transaction::Entity::find()
+        .select_only()
+        .column(transaction::Column::Id)
+        .join(JoinType::InnerJoin, transaction::Relation::Block.def())
+        .filter(block::Column::Slot.gt(*slot))
+        .into_model::<QueryIdResult>()
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|t| t.id)
+        .collect::<Vec<_>>();
+    // The outputs spent by the removed transactions are unspent again
+    for chunk in removed.chunks(QUERY_CHUNK_SIZE) {
+        transaction_output::Entity::update_many()
+            .col_expr(
+                transaction_output::Column::SpentTxId,
+                Expr::value(Option::<i64>::None),
+            )
+            .filter(transaction_output::Column::SpentTxId.is_in(chunk.iter().copied()))
+            .exec(db)
+            .await?;
+    }
     block::Entity::delete_many()
         .filter(block::Column::Slot.gt(*slot))
         .exec(db)
@@ -50,21 +110,44 @@ pub async fn rollback_to_slot(slot: &u64, db: &DatabaseConnection) -> anyhow::Re
     Ok(())
 }
 
+/// Insert the transaction with its outputs. Returns the transaction id and whether an already
+/// stored transaction was replaced.
 pub async fn insert_transaction(
     transaction: &TransactionRecord,
     block_id: i64,
+    valid: bool,
     db: &DatabaseConnection,
-) -> anyhow::Result<i64> {
+) -> anyhow::Result<(i64, bool)> {
+    let hash = hex::decode(transaction.hash.clone())?;
+    let existing_transaction_model = transaction::Entity::find()
+        .filter(transaction::Column::Hash.eq(hash.clone()))
+        .one(db)
+        .await?;
     let transaction_model = transaction::ActiveModel {
-        hash: Set(hex::decode(transaction.hash.clone())?),
+        id: match &existing_transaction_model {
+            Some(t) => Set(t.id),
+            None => NotSet,
+        },
+        hash: Set(hash),
         block_id: Set(block_id),
-        ..Default::default()
+        valid: Set(valid),
+    };
+    let replaced = existing_transaction_model.is_some();
+    let transaction_model = match existing_transaction_model {
+        Some(t) => {
+            // A re-seen transaction gets its outputs and derived rows written again
+            delete_transaction_data(t.id, db).await?;
+            transaction_model.update(db).await?
+        }
+        None => transaction_model.insert(db).await?,
     };
-    let transaction_model = transaction_model.insert(db).await?;
+
+    // Failed transactions only create their collateral return output
+    let outputs = utils::get_produced_outputs(transaction, valid);
 
     let mut addresses = HashSet::new();
     let mut tokens = HashSet::from([ADA_TOKEN.clone()]);
-    for output in transaction.outputs.iter().flatten() {
+    for (_, output) in outputs.iter() {
         addresses.insert(output.address.clone());
 
         for token in output.assets.iter().flatten() {
@@ -84,7 +167,7 @@ pub async fn insert_transaction(
             .map(|t| ((t.policy_id.clone(), t.name.clone()), t)),
     );
 
-    for (index, output) in transaction.outputs.iter().flatten().enumerate() {
+    for (index, output) in outputs.into_iter() {
         insert_output(
             output,
             &transaction_model,
@@ -96,7 +179,83 @@ pub async fn insert_transaction(
         .await?;
     }
 
-    Ok(transaction_model.id)
+    // Mark the stored outputs spent by the transaction, failed ones spend their collateral
+    let inputs = match valid {
+        true => &transaction.inputs,
+        false => &transaction.collateral_inputs,
+    };
+    let inputs: Vec<TxInputRecord> = inputs.iter().flatten().cloned().collect();
+    for chunk in inputs.chunks(QUERY_CHUNK_SIZE) {
+        let spent = transaction_output::Entity::find()
+            .select_only()
+            .column(transaction_output::Column::Id)
+            .join(
+                JoinType::InnerJoin,
+                transaction_output::Relation::Transaction.def(),
+            )
+            .filter(get_output_condition(chunk)?)
+            .into_model::<QueryIdResult>()
+            .all(db)
+            .await?;
+        if spent.is_empty() {
+            continue;
+        }
+        transaction_output::Entity::update_many()
+            .col_expr(
+                transaction_output::Column::SpentTxId,
+                Expr::value(transaction_model.id),
+            )
+            .filter(transaction_output::Column::Id.is_in(spent.into_iter().map(|o| o.id)))
+            .exec(db)
+            .await?;
+    }
+
+    Ok((transaction_model.id, replaced))
+}
+
+async fn delete_transaction_data(tx_id: i64, db: &DatabaseConnection) -> anyhow::Result<()> {
+    // The outputs spent by the transaction are marked again when it is stored
+    transaction_output::Entity::update_many()
+        .col_expr(
+            transaction_output::Column::SpentTxId,
+            Expr::value(Option::<i64>::None),
+        )
+        .filter(transaction_output::Column::SpentTxId.eq(tx_id))
+        .exec(db)
+        .await?;
+    // Token transfers are removed together with the outputs by the cascade
+    transaction_output::Entity::delete_many()
+        .filter(transaction_output::Column::TxId.eq(tx_id))
+        .exec(db)
+        .await?;
+    price_update::Entity::delete_many()
+        .filter(price_update::Column::TxId.eq(tx_id))
+        .exec(db)
+        .await?;
+    swap::Entity::delete_many()
+        .filter(swap::Column::TxId.eq(tx_id))
+        .exec(db)
+        .await?;
+    batch::Entity::delete_many()
+        .filter(batch::Column::TxId.eq(tx_id))
+        .exec(db)
+        .await?;
+    mev_alert::Entity::delete_many()
+        .filter(mev_alert::Column::TxId.eq(tx_id))
+        .exec(db)
+        .await?;
+    order::Entity::delete_many()
+        .filter(order::Column::TxId.eq(tx_id))
+        .exec(db)
+        .await?;
+    // The orders spent by the transaction are closed again when it is processed
+    order::Entity::update_many()
+        .col_expr(order::Column::SpentTxId, Expr::value(Option::<i64>::None))
+        .col_expr(order::Column::Outcome, Expr::value(Option::<String>::None))
+        .filter(order::Column::SpentTxId.eq(tx_id))
+        .exec(db)
+        .await?;
+    Ok(())
 }
 
 async fn insert_missing_addresses(
@@ -126,12 +285,18 @@ async fn insert_missing_addresses(
     if missing_address_models.is_empty() {
         return Ok(found_address_models);
     }
-    let added_address_models = address::Entity::insert_many(missing_address_models)
-        .exec_many_with_returning(db)
-        .await?;
+    let added_address_models = match db.get_database_backend() {
+        // RETURNING of multiple rows is supported only for Postgres
+        DbBackend::Sqlite => insert_one_by_one(missing_address_models, db).await?,
+        _ => {
+            address::Entity::insert_many(missing_address_models)
+                .exec_many_with_returning(db)
+                .await?
+        }
+    };
     Ok(found_address_models
         .into_iter()
-        .chain(added_address_models.into_iter())
+        .chain(added_address_models)
         .collect())
 }
 
@@ -166,24 +331,55 @@ async fn insert_missing_tokens(
         .collect();
     let missing_token_models: Vec<token::ActiveModel> = missing_tokens
         .iter()
-        .map(|(p, n)| token::ActiveModel {
-            policy_id: Set(p.clone()),
-            name: Set(n.clone()),
-            ..Default::default()
+        .map(|(p, n)| {
+            let metadata = registry::get(&Asset {
+                policy_id: hex::encode(p),
+                name: hex::encode(n),
+            })
+            .unwrap_or_default();
+            token::ActiveModel {
+                policy_id: Set(p.clone()),
+                name: Set(n.clone()),
+                decimals: Set(metadata.decimals),
+                ticker: Set(metadata.ticker),
+                display_name: Set(metadata.display_name),
+                logo: Set(metadata.logo),
+                ..Default::default()
+            }
         })
         .collect();
     if missing_token_models.is_empty() {
         return Ok(found_token_models);
     }
-    let added_token_models = token::Entity::insert_many(missing_token_models)
-        .exec_many_with_returning(db)
-        .await?;
+    let added_token_models = match db.get_database_backend() {
+        DbBackend::Sqlite => insert_one_by_one(missing_token_models, db).await?,
+        _ => {
+            token::Entity::insert_many(missing_token_models)
+                .exec_many_with_returning(db)
+                .await?
+        }
+    };
     Ok(found_token_models
         .into_iter()
-        .chain(added_token_models.into_iter())
+        .chain(added_token_models)
         .collect())
 }
 
+async fn insert_one_by_one<A>(
+    models: Vec<A>,
+    db: &DatabaseConnection,
+) -> anyhow::Result<Vec<<A::Entity as EntityTrait>::Model>>
+where
+    A: ActiveModelTrait + ActiveModelBehavior + Send,
+    <A::Entity as EntityTrait>::Model: IntoActiveModel<A>,
+{
+    let mut inserted = Vec::new();
+    for model in models.into_iter() {
+        inserted.push(model.insert(db).await?);
+    }
+    Ok(inserted)
+}
+
 async fn insert_output(
     output: &TxOutputRecord,
     transaction_model: &transaction::Model,
@@ -239,9 +435,9 @@ pub async fn insert_price_update(
     tx_id: i64,
     script_hash: &[u8],
     token1_id: i64,
-    asset1_amount: i64,
     token2_id: i64,
-    asset2_amount: i64,
+    amounts: (i64, i64),
+    output_index: Option<i64>,
     db: &DatabaseConnection,
 ) -> anyhow::Result<()> {
     let price_update_model = price_update::ActiveModel {
@@ -249,8 +445,9 @@ pub async fn insert_price_update(
         script_hash: Set(script_hash.to_vec()),
         token1_id: Set(token1_id),
         token2_id: Set(token2_id),
-        amount1: Set(asset1_amount),
-        amount2: Set(asset2_amount),
+        amount1: Set(amounts.0),
+        amount2: Set(amounts.1),
+        output_index: Set(output_index),
         ..Default::default()
     };
     price_update_model.insert(db).await?;
@@ -260,6 +457,7 @@ pub async fn insert_price_update(
 pub async fn insert_swap(
     tx_id: i64,
     script_hash: &[u8],
+    position: i64,
     swap: &SwapInfo,
     db: &DatabaseConnection,
 ) -> anyhow::Result<()> {
@@ -271,241 +469,1878 @@ pub async fn insert_swap(
         amount1: Set(swap.amount1),
         amount2: Set(swap.amount2),
         direction: Set(swap.direction == "Sell"),
+        payment_credential: Set(swap
+            .payment_credential
+            .as_deref()
+            .map(hex::decode)
+            .transpose()?),
+        stake_credential: Set(swap
+            .stake_credential
+            .as_deref()
+            .map(hex::decode)
+            .transpose()?),
+        order_tx_hash: Set(swap
+            .origin
+            .output
+            .as_ref()
+            .map(|o| hex::decode(&o.tx_hash))
+            .transpose()?),
+        order_index: Set(swap.origin.output.as_ref().map(|o| o.index as i64)),
+        min_receive: Set(swap.min_receive.map(|m| m as i64)),
+        deadline: Set(swap
+            .deadline
+            .and_then(|d| DateTime::from_timestamp_opt(d, 0))),
+        slippage: Set(swap.slippage),
+        position: Set(position),
         ..Default::default()
     };
     swap_model.insert(db).await?;
     Ok(())
 }
 
-pub async fn get_token_id(asset: &Asset, db: &DatabaseConnection) -> anyhow::Result<i64> {
-    Ok(token::Entity::find()
-        .filter(
-            token::Column::PolicyId
-                .eq(hex::decode(&asset.policy_id)?)
-                .and(token::Column::Name.eq(hex::decode(&asset.name)?)),
-        )
-        .one(db)
-        .await?
-        .ok_or_else(|| anyhow::anyhow!("Token not found"))?
-        .id)
+/// Insert the order placed at the output of the transaction.
+pub async fn insert_order(
+    tx_id: i64,
+    script_hash: &[u8],
+    output_index: usize,
+    decoded: &OrderDatum,
+    datum: Option<&serde_json::Value>,
+    db: &DatabaseConnection,
+) -> anyhow::Result<()> {
+    let (payment_credential, stake_credential) = decoded
+        .owner
+        .as_deref()
+        .and_then(utils::get_credentials)
+        .unwrap_or_default();
+    let order_model = order::ActiveModel {
+        script_hash: Set(script_hash.to_vec()),
+        tx_id: Set(tx_id),
+        output_index: Set(output_index as i64),
+        payment_credential: Set(payment_credential),
+        stake_credential: Set(stake_credential),
+        min_receive: Set(decoded.min_receive.map(|m| m as i64)),
+        deadline: Set(decoded
+            .deadline
+            .and_then(|d| DateTime::from_timestamp_opt(d, 0))),
+        datum: Set(datum.map(|d| d.to_string())),
+        ..Default::default()
+    };
+    order_model.insert(db).await?;
+    Ok(())
 }
 
-pub async fn get_latest_prices(db: &DatabaseConnection) -> anyhow::Result<Vec<ExchangeRate>> {
-    // The raw SQL query here is rather unlucky, but we need to join the token table twice,
-    // and the sea-orm version usde by us (dcSpark's fork which implements
-    // exec_many_with_returning) doesn't seem to support join aliases.
-    // TODO figure out how to do both multi-joining here and exec_many_with_returning above.
+/// References of the open orders spent by the inputs, whether they are still cached or not.
+pub async fn get_spent_orders(
+    inputs: &[TxInputRecord],
+    db: &DatabaseConnection,
+) -> anyhow::Result<HashSet<(String, u64)>> {
+    #[derive(FromQueryResult)]
+    struct QueryOrderResult {
+        output_index: i64,
+        hash: Vec<u8>,
+    }
 
-    #[derive(Debug, FromQueryResult)]
-    struct RawExchangeRate {
-        script_hash: Vec<u8>,
-        t1_id: i64,
-        t2_id: i64,
-        amount1: i64,
-        amount2: i64,
+    let mut spent = HashSet::new();
+    for chunk in inputs.chunks(QUERY_CHUNK_SIZE) {
+        let mut condition = Condition::any();
+        for input in chunk.iter() {
+            condition = condition.add(
+                order::Column::OutputIndex
+                    .eq(input.index)
+                    .and(transaction::Column::Hash.eq(hex::decode(&input.tx_id)?)),
+            );
+        }
+        let orders = order::Entity::find()
+            .select_only()
+            .column(order::Column::OutputIndex)
+            .column(transaction::Column::Hash)
+            .join(JoinType::InnerJoin, order::Relation::Transaction.def())
+            .filter(order::Column::SpentTxId.is_null())
+            .filter(condition)
+            .into_model::<QueryOrderResult>()
+            .all(db)
+            .await?;
+        spent.extend(
+            orders
+                .into_iter()
+                .map(|o| (hex::encode(&o.hash), o.output_index as u64)),
+        );
     }
+    Ok(spent)
+}
 
-    let raw_exchange_rates: Vec<RawExchangeRate> =
-        RawExchangeRate::find_by_statement(Statement::from_sql_and_values(
-            DbBackend::Postgres,
-            r#"
-            SELECT
-                script_hash,
-                t1.id AS t1_id,
-                t2.id AS t2_id,
-                amount1,
-                amount2
+/// Close the open orders spent by the transaction. Orders spent without being executed are
+/// refunded when a pool took part in the transaction (a batch), otherwise cancelled by the owner.
+pub async fn close_orders(
+    transaction: &TransactionRecord,
+    tx_id: i64,
+    executed: &[OutputReference],
+    batch: bool,
+    db: &DatabaseConnection,
+) -> anyhow::Result<()> {
+    #[derive(FromQueryResult)]
+    struct QueryOrderResult {
+        id: i64,
+        output_index: i64,
+        hash: Vec<u8>,
+    }
 
-            FROM price_update
-            JOIN token AS t1 ON t1.id = price_update.token1_id
-            JOIN token AS t2 ON t2.id = price_update.token2_id
-            WHERE (script_hash, token1_id, token2_id, tx_id) IN (
-                SELECT script_hash, token1_id, token2_id, MAX(tx_id)
-                FROM price_update
-                GROUP BY script_hash, token1_id, token2_id
-            )
-            "#,
-            vec![],
-        ))
+    let mut condition = Condition::any();
+    for input in transaction.inputs.iter().flatten() {
+        condition = condition.add(
+            order::Column::OutputIndex
+                .eq(input.index)
+                .and(transaction::Column::Hash.eq(hex::decode(&input.tx_id)?)),
+        );
+    }
+    let spent = order::Entity::find()
+        .select_only()
+        .column(order::Column::Id)
+        .column(order::Column::OutputIndex)
+        .column(transaction::Column::Hash)
+        .join(JoinType::InnerJoin, order::Relation::Transaction.def())
+        .filter(order::Column::SpentTxId.is_null())
+        .filter(condition)
+        .into_model::<QueryOrderResult>()
         .all(db)
-        .await
-        .unwrap();
+        .await?;
 
-    Ok(raw_exchange_rates
-        .iter()
-        .map(|r| ExchangeRate {
-            script_hash: hex::encode(r.script_hash.clone()),
-            asset1: r.t1_id,
-            asset2: r.t2_id,
-            rate: r.amount1 as f64 / r.amount2 as f64,
-        })
-        .collect())
+    for spent_order in spent.iter() {
+        let tx_hash = hex::encode(&spent_order.hash);
+        let outcome = if executed
+            .iter()
+            .any(|o| o.tx_hash == tx_hash && o.index as i64 == spent_order.output_index)
+        {
+            OrderStatus::Executed
+        } else if batch {
+            OrderStatus::Refunded
+        } else {
+            OrderStatus::Cancelled
+        };
+        order::Entity::update_many()
+            .col_expr(order::Column::SpentTxId, Expr::value(tx_id))
+            .col_expr(order::Column::Outcome, Expr::value(outcome.name()))
+            .filter(order::Column::Id.eq(spent_order.id))
+            .exec(db)
+            .await?;
+    }
+    Ok(())
 }
 
-pub async fn get_assets(db: &DatabaseConnection) -> anyhow::Result<HashMap<i64, Asset>> {
-    let tokens = token::Entity::find().all(db).await?;
-    Ok(tokens
-        .iter()
-        .map(|t| {
+/// Placement of the orders as the transaction id and the output index, which follow the order
+/// of the chain. Orders placed before the order tracking started are missing.
+pub async fn get_order_placements(
+    orders: &[OutputReference],
+    db: &DatabaseConnection,
+) -> anyhow::Result<HashMap<(String, u64), (i64, i64)>> {
+    #[derive(FromQueryResult)]
+    struct QueryOrderResult {
+        tx_id: i64,
+        output_index: i64,
+        hash: Vec<u8>,
+    }
+
+    if orders.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let mut condition = Condition::any();
+    for output in orders.iter() {
+        condition = condition.add(
+            order::Column::OutputIndex
+                .eq(output.index)
+                .and(transaction::Column::Hash.eq(hex::decode(&output.tx_hash)?)),
+        );
+    }
+    Ok(order::Entity::find()
+        .select_only()
+        .column(order::Column::TxId)
+        .column(order::Column::OutputIndex)
+        .column(transaction::Column::Hash)
+        .join(JoinType::InnerJoin, order::Relation::Transaction.def())
+        .filter(condition)
+        .into_model::<QueryOrderResult>()
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|o| {
             (
-                t.id,
-                Asset {
-                    policy_id: hex::encode(t.policy_id.clone()),
-                    name: hex::encode(t.name.clone()),
-                },
+                (hex::encode(o.hash), o.output_index as u64),
+                (o.tx_id, o.output_index),
             )
         })
         .collect())
 }
 
-pub async fn get_token_price_history(
-    asset_id1: i64,
-    asset_id2: i64,
+pub async fn insert_batch(
+    tx_id: i64,
+    batch: &BatchInfo,
+    db: &DatabaseConnection,
+) -> anyhow::Result<()> {
+    let batch_model = batch::ActiveModel {
+        tx_id: Set(tx_id),
+        script_hash: Set(hex::decode(&batch.script_hash)?),
+        batcher: Set(batch.batcher.as_deref().map(hex::decode).transpose()?),
+        orders: Set(batch.orders as i64),
+        batcher_fee: Set(batch.batcher_fee as i64),
+        deposit: Set(batch.deposit as i64),
+        tx_fee: Set(batch.tx_fee as i64),
+        placed_orders: Set(batch.placed_orders as i64),
+        inversions: Set(batch.inversions as i64),
+        ..Default::default()
+    };
+    batch_model.insert(db).await?;
+    Ok(())
+}
+
+/// Latest batches, optionally of a single pool or batcher.
+pub async fn get_batches(
+    script_hash: Option<Vec<u8>>,
+    batcher: Option<Vec<u8>>,
     count: u64,
     db: &DatabaseConnection,
-) -> anyhow::Result<Vec<ExchangeHistory>> {
-    let data = price_update::Entity::find()
-        .filter(price_update::Column::Token1Id.eq(asset_id1))
-        .filter(price_update::Column::Token2Id.eq(asset_id2))
-        .order_by(price_update::Column::TxId, Order::Desc)
+) -> anyhow::Result<Vec<BatchInfo>> {
+    #[derive(FromQueryResult)]
+    struct QueryBatchResult {
+        script_hash: Vec<u8>,
+        batcher: Option<Vec<u8>>,
+        orders: i64,
+        batcher_fee: i64,
+        deposit: i64,
+        tx_fee: i64,
+        placed_orders: i64,
+        inversions: i64,
+        hash: Vec<u8>,
+        timestamp: Option<DateTime>,
+    }
+
+    let mut condition = Condition::all();
+    if let Some(script_hash) = script_hash {
+        condition = condition.add(batch::Column::ScriptHash.eq(script_hash));
+    }
+    if let Some(batcher) = batcher {
+        condition = condition.add(batch::Column::Batcher.eq(batcher));
+    }
+    let data = batch::Entity::find()
+        .select_only()
+        .column(batch::Column::ScriptHash)
+        .column(batch::Column::Batcher)
+        .column(batch::Column::Orders)
+        .column(batch::Column::BatcherFee)
+        .column(batch::Column::Deposit)
+        .column(batch::Column::TxFee)
+        .column(batch::Column::PlacedOrders)
+        .column(batch::Column::Inversions)
+        .column(transaction::Column::Hash)
+        .column(block::Column::Timestamp)
+        .join(JoinType::InnerJoin, batch::Relation::Transaction.def())
+        .join(JoinType::InnerJoin, transaction::Relation::Block.def())
+        .filter(condition)
+        .order_by(batch::Column::Id, Order::Desc)
         .limit(count)
+        .into_model::<QueryBatchResult>()
         .all(db)
         .await?;
 
     Ok(data
-        .iter()
-        .map(|p| ExchangeHistory {
-            amount1: p.amount1,
-            amount2: p.amount2,
-            rate: p.amount1 as f64 / p.amount2 as f64,
-            tx_id: p.tx_id,
+        .into_iter()
+        .map(|b| BatchInfo {
+            script_hash: hex::encode(b.script_hash),
+            tx_hash: hex::encode(b.hash),
+            batcher: b.batcher.map(hex::encode),
+            orders: b.orders as u64,
+            batcher_fee: b.batcher_fee as u64,
+            deposit: b.deposit as u64,
+            tx_fee: b.tx_fee as u64,
+            placed_orders: b.placed_orders as u64,
+            inversions: b.inversions as u64,
+            timestamp: b.timestamp.map(|t| t.timestamp()),
         })
         .collect())
 }
 
-pub async fn get_swap_history(
-    asset_id1: i64,
-    asset_id2: i64,
-    count: u64,
+/// Totals of every batcher of every pool, optionally of a single pool.
+pub async fn get_batcher_stats(
+    script_hash: Option<Vec<u8>>,
     db: &DatabaseConnection,
-) -> anyhow::Result<Vec<SwapHistory>> {
-    let data = swap::Entity::find()
-        .filter(swap::Column::Token1Id.eq(asset_id1))
-        .filter(swap::Column::Token2Id.eq(asset_id2))
-        .order_by(swap::Column::TxId, Order::Desc)
-        .limit(count)
-        .order_by(swap::Column::TxId, Order::Asc)
+) -> anyhow::Result<Vec<BatcherStats>> {
+    #[derive(FromQueryResult)]
+    struct QueryBatcherResult {
+        script_hash: Vec<u8>,
+        batcher: Option<Vec<u8>>,
+        batches: i64,
+        orders: i64,
+        batcher_fee: i64,
+        deposit: i64,
+        tx_fee: i64,
+        inversions: i64,
+        pairs: i64,
+    }
+
+    let mut condition = Condition::all();
+    if let Some(script_hash) = script_hash {
+        condition = condition.add(batch::Column::ScriptHash.eq(script_hash));
+    }
+    let data = batch::Entity::find()
+        .select_only()
+        .column(batch::Column::ScriptHash)
+        .column(batch::Column::Batcher)
+        // Postgres counts and sums big integers into numerics
+        .column_as(Expr::cust("CAST(COUNT(*) AS BIGINT)"), "batches")
+        .column_as(Expr::cust("CAST(SUM(orders) AS BIGINT)"), "orders")
+        .column_as(
+            Expr::cust("CAST(SUM(batcher_fee) AS BIGINT)"),
+            "batcher_fee",
+        )
+        .column_as(Expr::cust("CAST(SUM(deposit) AS BIGINT)"), "deposit")
+        .column_as(Expr::cust("CAST(SUM(tx_fee) AS BIGINT)"), "tx_fee")
+        .column_as(Expr::cust("CAST(SUM(inversions) AS BIGINT)"), "inversions")
+        .column_as(
+            Expr::cust("CAST(SUM(placed_orders * (placed_orders - 1) / 2) AS BIGINT)"),
+            "pairs",
+        )
+        .filter(condition)
+        .group_by(batch::Column::ScriptHash)
+        .group_by(batch::Column::Batcher)
+        .into_model::<QueryBatcherResult>()
         .all(db)
         .await?;
 
     Ok(data
-        .iter()
-        .map(|p| SwapHistory {
-            amount1: p.amount1,
-            amount2: p.amount2,
-            tx_id: p.tx_id,
-            direction: match p.direction {
-                true => "Buy".to_string(),
-                false => "Sell".to_string(),
-            },
+        .into_iter()
+        .map(|b| BatcherStats {
+            script_hash: hex::encode(b.script_hash),
+            batcher: b.batcher.map(hex::encode),
+            batches: b.batches,
+            orders: b.orders,
+            batcher_fee: b.batcher_fee,
+            deposit: b.deposit,
+            tx_fee: b.tx_fee,
+            inversions: b.inversions,
+            fairness: utils::get_rate(b.pairs - b.inversions, b.pairs),
         })
         .collect())
 }
 
-pub async fn get_utxo_input(
-    inputs: &[TxInputRecord],
+pub async fn insert_alert(
+    tx_id: i64,
+    alert: &MevAlert,
     db: &DatabaseConnection,
-) -> anyhow::Result<Vec<Option<TxOutputRecord>>> {
+) -> anyhow::Result<()> {
+    let alert_model = mev_alert::ActiveModel {
+        tx_id: Set(tx_id),
+        kind: Set(alert.kind.clone()),
+        script_hash: Set(hex::decode(&alert.script_hash)?),
+        token1_id: Set(alert.asset1),
+        token2_id: Set(alert.asset2),
+        attacker: Set(alert.attacker.as_deref().map(hex::decode).transpose()?),
+        victims: Set(serde_json::to_string(&alert.victims)?),
+        swaps: Set(serde_json::to_string(&alert.swaps)?),
+        ..Default::default()
+    };
+    alert_model.insert(db).await?;
+    Ok(())
+}
+
+/// Latest MEV alerts, optionally of a single kind or pool.
+pub async fn get_alerts(
+    kind: Option<AlertKind>,
+    script_hash: Option<Vec<u8>>,
+    count: u64,
+    db: &DatabaseConnection,
+) -> anyhow::Result<Vec<MevAlert>> {
     #[derive(FromQueryResult)]
-    struct QueryOutputResult {
-        id: i64,
-        index: i32,
-        datum_hash: Option<String>,
-        hash: Vec<u8>,
-        payload: String,
+    struct QueryAlertResult {
+        kind: String,
+        script_hash: Vec<u8>,
+        token1_id: i64,
+        token2_id: i64,
+        attacker: Option<Vec<u8>>,
+        victims: String,
+        swaps: String,
+        tx_hash: Vec<u8>,
+        block_hash: Vec<u8>,
+        slot: i64,
+        timestamp: Option<DateTime>,
     }
 
-    let mut condition = Condition::any();
-    for input in inputs.iter() {
-        condition = condition.add(
-            transaction_output::Column::Index
-                .eq(input.index)
-                .and(transaction::Column::Hash.eq(hex::decode(&input.tx_id)?)),
-        );
+    let mut condition = Condition::all();
+    if let Some(kind) = kind {
+        condition = condition.add(mev_alert::Column::Kind.eq(kind.name()));
     }
-
-    let output_query = transaction_output::Entity::find()
+    if let Some(script_hash) = script_hash {
+        condition = condition.add(mev_alert::Column::ScriptHash.eq(script_hash));
+    }
+    let data = mev_alert::Entity::find()
         .select_only()
-        .column(transaction_output::Column::Id)
-        .column(transaction_output::Column::Index)
-        .column(transaction_output::Column::DatumHash)
-        .column(transaction::Column::Hash)
-        .column(address::Column::Payload)
-        .join(
-            JoinType::InnerJoin,
-            transaction_output::Relation::Transaction.def(),
-        )
-        .join(
-            JoinType::InnerJoin,
-            transaction_output::Relation::Address.def(),
-        )
+        .column(mev_alert::Column::Kind)
+        .column(mev_alert::Column::ScriptHash)
+        .column(mev_alert::Column::Token1Id)
+        .column(mev_alert::Column::Token2Id)
+        .column(mev_alert::Column::Attacker)
+        .column(mev_alert::Column::Victims)
+        .column(mev_alert::Column::Swaps)
+        .column_as(transaction::Column::Hash, "tx_hash")
+        .column_as(block::Column::Hash, "block_hash")
+        .column(block::Column::Slot)
+        .column(block::Column::Timestamp)
+        .join(JoinType::InnerJoin, mev_alert::Relation::Transaction.def())
+        .join(JoinType::InnerJoin, transaction::Relation::Block.def())
         .filter(condition)
-        .into_model::<QueryOutputResult>()
+        .order_by(mev_alert::Column::Id, Order::Desc)
+        .limit(count)
+        .into_model::<QueryAlertResult>()
         .all(db)
         .await?;
 
-    let mut result = Vec::new();
-
-    for output in inputs.iter().map(|input| {
-        output_query.iter().find(|output| {
-            input.tx_id == hex::encode(&output.hash) && input.index == (output.index as u64)
-        })
-    }) {
-        result.push(match output {
-            Some(o) => {
-                #[derive(FromQueryResult)]
-                struct QueryTokenResult {
-                    amount: i64,
-                    policy_id: Vec<u8>,
-                    name: Vec<u8>,
+    Ok(data
+        .into_iter()
+        .map(|a| {
+            let swaps: Vec<String> = serde_json::from_str(&a.swaps).unwrap_or_default();
+            MevAlert {
+                event_id: utils::get_alert_event_id(&a.kind, &swaps),
+                kind: a.kind,
+                script_hash: hex::encode(a.script_hash),
+                asset1: a.token1_id,
+                asset2: a.token2_id,
+                attacker: a.attacker.map(hex::encode),
+                victims: serde_json::from_str(&a.victims).unwrap_or_default(),
+                swaps,
+                tx_hash: hex::encode(a.tx_hash),
+                block_hash: hex::encode(a.block_hash),
+                slot: a.slot as u64,
+                timestamp: a.timestamp.map(|t| t.timestamp()),
+            }
+        })
+        .collect())
+}
+
+/// Store the arbitrage opportunities of the block, replacing the ones found when the block was
+/// processed before.
+pub async fn insert_arbitrage_opportunities(
+    block_id: i64,
+    opportunities: &[ArbitrageOpportunity],
+    db: &DatabaseConnection,
+) -> anyhow::Result<()> {
+    arbitrage_opportunity::Entity::delete_many()
+        .filter(arbitrage_opportunity::Column::BlockId.eq(block_id))
+        .exec(db)
+        .await?;
+    for opportunity in opportunities.iter() {
+        let opportunity_model = arbitrage_opportunity::ActiveModel {
+            block_id: Set(block_id),
+            token1_id: Set(opportunity.asset1),
+            token2_id: Set(opportunity.asset2),
+            buy_script_hash: Set(hex::decode(&opportunity.buy_pool)?),
+            sell_script_hash: Set(hex::decode(&opportunity.sell_pool)?),
+            buy_rate: Set(opportunity.buy_rate),
+            sell_rate: Set(opportunity.sell_rate),
+            amount_in: Set(opportunity.amount_in as i64),
+            amount_bought: Set(opportunity.amount_bought as i64),
+            amount_out: Set(opportunity.amount_out as i64),
+            batcher_fee: Set(opportunity.batcher_fee as i64),
+            profit: Set(opportunity.profit),
+            ..Default::default()
+        };
+        opportunity_model.insert(db).await?;
+    }
+    Ok(())
+}
+
+/// Latest arbitrage opportunities, optionally of a single pair. The tokens can be given in
+/// either order.
+pub async fn get_arbitrage_opportunities(
+    pair: Option<(i64, i64)>,
+    count: u64,
+    db: &DatabaseConnection,
+) -> anyhow::Result<Vec<ArbitrageOpportunity>> {
+    #[derive(FromQueryResult)]
+    struct QueryOpportunityResult {
+        token1_id: i64,
+        token2_id: i64,
+        buy_script_hash: Vec<u8>,
+        sell_script_hash: Vec<u8>,
+        buy_rate: Option<Decimal>,
+        sell_rate: Option<Decimal>,
+        amount_in: i64,
+        amount_bought: i64,
+        amount_out: i64,
+        batcher_fee: i64,
+        profit: i64,
+        hash: Vec<u8>,
+        slot: i64,
+        timestamp: Option<DateTime>,
+    }
+
+    let mut condition = Condition::all();
+    if let Some((asset1, asset2)) = pair {
+        condition = condition.add(
+            arbitrage_opportunity::Column::Token1Id
+                .is_in([asset1, asset2])
+                .and(arbitrage_opportunity::Column::Token2Id.is_in([asset1, asset2])),
+        );
+    }
+    let data = arbitrage_opportunity::Entity::find()
+        .select_only()
+        .column(arbitrage_opportunity::Column::Token1Id)
+        .column(arbitrage_opportunity::Column::Token2Id)
+        .column(arbitrage_opportunity::Column::BuyScriptHash)
+        .column(arbitrage_opportunity::Column::SellScriptHash)
+        .column(arbitrage_opportunity::Column::BuyRate)
+        .column(arbitrage_opportunity::Column::SellRate)
+        .column(arbitrage_opportunity::Column::AmountIn)
+        .column(arbitrage_opportunity::Column::AmountBought)
+        .column(arbitrage_opportunity::Column::AmountOut)
+        .column(arbitrage_opportunity::Column::BatcherFee)
+        .column(arbitrage_opportunity::Column::Profit)
+        .column(block::Column::Hash)
+        .column(block::Column::Slot)
+        .column(block::Column::Timestamp)
+        .join(
+            JoinType::InnerJoin,
+            arbitrage_opportunity::Relation::Block.def(),
+        )
+        .filter(condition)
+        .order_by(arbitrage_opportunity::Column::Id, Order::Desc)
+        .limit(count)
+        .into_model::<QueryOpportunityResult>()
+        .all(db)
+        .await?;
+
+    Ok(data
+        .into_iter()
+        .map(|o| {
+            let block_hash = hex::encode(o.hash);
+            let buy_pool = hex::encode(o.buy_script_hash);
+            let sell_pool = hex::encode(o.sell_script_hash);
+            ArbitrageOpportunity {
+                event_id: utils::get_arbitrage_event_id(
+                    &block_hash,
+                    (o.token1_id, o.token2_id),
+                    &buy_pool,
+                    &sell_pool,
+                ),
+                asset1: o.token1_id,
+                asset2: o.token2_id,
+                buy_pool,
+                sell_pool,
+                buy_rate: o.buy_rate,
+                sell_rate: o.sell_rate,
+                amount_in: o.amount_in as u64,
+                amount_bought: o.amount_bought as u64,
+                amount_out: o.amount_out as u64,
+                batcher_fee: o.batcher_fee as u64,
+                profit: o.profit,
+                block_hash,
+                slot: o.slot as u64,
+                timestamp: o.timestamp.map(|t| t.timestamp()),
+            }
+        })
+        .collect())
+}
+
+/// Latest orders in the given status, optionally of a single pool. Open orders past their
+/// deadline (compared to the latest block) are reported as expired.
+pub async fn get_orders(
+    script_hash: Option<Vec<u8>>,
+    status: OrderStatus,
+    count: u64,
+    db: &DatabaseConnection,
+) -> anyhow::Result<Vec<OrderInfo>> {
+    #[derive(FromQueryResult)]
+    struct QueryOrderResult {
+        script_hash: Vec<u8>,
+        output_index: i64,
+        payment_credential: Option<Vec<u8>>,
+        stake_credential: Option<Vec<u8>>,
+        min_receive: Option<i64>,
+        deadline: Option<DateTime>,
+        datum: Option<String>,
+        spent_tx_id: Option<i64>,
+        outcome: Option<String>,
+        tx_hash: Vec<u8>,
+        timestamp: Option<DateTime>,
+    }
+
+    #[derive(FromQueryResult)]
+    struct QuerySpentResult {
+        id: i64,
+        hash: Vec<u8>,
+        timestamp: Option<DateTime>,
+    }
+
+    let now = get_last_block_timestamp(db).await?.unwrap_or_default();
+    let now = DateTime::from_timestamp_opt(now, 0).ok_or_else(|| anyhow::anyhow!("Bad time"))?;
+
+    let mut condition = Condition::all();
+    if let Some(script_hash) = script_hash {
+        condition = condition.add(order::Column::ScriptHash.eq(script_hash));
+    }
+    let condition = match status {
+        OrderStatus::Open => condition.add(order::Column::SpentTxId.is_null()).add(
+            Condition::any()
+                .add(order::Column::Deadline.is_null())
+                .add(order::Column::Deadline.gte(now)),
+        ),
+        OrderStatus::Expired => condition
+            .add(order::Column::SpentTxId.is_null())
+            .add(order::Column::Deadline.lt(now)),
+        _ => condition
+            .add(order::Column::SpentTxId.is_not_null())
+            .add(order::Column::Outcome.eq(status.name())),
+    };
+
+    let data = order::Entity::find()
+        .select_only()
+        .column(order::Column::ScriptHash)
+        .column(order::Column::OutputIndex)
+        .column(order::Column::PaymentCredential)
+        .column(order::Column::StakeCredential)
+        .column(order::Column::MinReceive)
+        .column(order::Column::Deadline)
+        .column(order::Column::Datum)
+        .column(order::Column::SpentTxId)
+        .column(order::Column::Outcome)
+        .column_as(transaction::Column::Hash, "tx_hash")
+        .column(block::Column::Timestamp)
+        .join(JoinType::InnerJoin, order::Relation::Transaction.def())
+        .join(JoinType::InnerJoin, transaction::Relation::Block.def())
+        .filter(condition)
+        .order_by(order::Column::Id, Order::Desc)
+        .limit(count)
+        .into_model::<QueryOrderResult>()
+        .all(db)
+        .await?;
+
+    let spent: HashMap<i64, QuerySpentResult> = transaction::Entity::find()
+        .select_only()
+        .column(transaction::Column::Id)
+        .column(transaction::Column::Hash)
+        .column(block::Column::Timestamp)
+        .join(JoinType::InnerJoin, transaction::Relation::Block.def())
+        .filter(transaction::Column::Id.is_in(data.iter().filter_map(|o| o.spent_tx_id)))
+        .into_model::<QuerySpentResult>()
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|t| (t.id, t))
+        .collect();
+
+    Ok(data
+        .into_iter()
+        .map(|o| {
+            let closed = o.spent_tx_id.and_then(|id| spent.get(&id));
+            let created_at = o.timestamp.map(|t| t.timestamp());
+            let closed_at = closed.and_then(|t| t.timestamp).map(|t| t.timestamp());
+            let status = match &o.outcome {
+                Some(outcome) if o.spent_tx_id.is_some() => outcome.clone(),
+                _ if o.deadline.filter(|d| *d < now).is_some() => {
+                    OrderStatus::Expired.name().to_string()
                 }
-                let mut assets = Vec::new();
-                let mut out = TxOutputRecord {
+                _ => OrderStatus::Open.name().to_string(),
+            };
+            OrderInfo {
+                script_hash: hex::encode(o.script_hash),
+                output: OutputReference {
+                    tx_hash: hex::encode(o.tx_hash),
+                    index: o.output_index as u64,
+                },
+                payment_credential: o.payment_credential.map(hex::encode),
+                stake_credential: o.stake_credential.map(hex::encode),
+                min_receive: o.min_receive.map(|m| m as u64),
+                deadline: o.deadline.map(|d| d.timestamp()),
+                datum: o.datum.and_then(|d| serde_json::from_str(&d).ok()),
+                time_to_fill: match (
+                    status == OrderStatus::Executed.name(),
+                    created_at,
+                    closed_at,
+                ) {
+                    (true, Some(created_at), Some(closed_at)) => Some(closed_at - created_at),
+                    _ => None,
+                },
+                status,
+                created_at,
+                closed_tx_hash: closed.map(|t| hex::encode(&t.hash)),
+                closed_at,
+            }
+        })
+        .collect())
+}
+
+/// Id of the stored token, `None` when the token is not stored yet.
+pub async fn get_token_id(asset: &Asset, db: &DatabaseConnection) -> anyhow::Result<Option<i64>> {
+    Ok(token::Entity::find()
+        .filter(
+            token::Column::PolicyId
+                .eq(hex::decode(&asset.policy_id)?)
+                .and(token::Column::Name.eq(hex::decode(&asset.name)?)),
+        )
+        .one(db)
+        .await?
+        .map(|t| t.id))
+}
+
+#[derive(Debug, FromQueryResult)]
+struct RawPriceUpdate {
+    script_hash: Vec<u8>,
+    t1_id: i64,
+    t2_id: i64,
+    t1_policy_id: Vec<u8>,
+    t1_name: Vec<u8>,
+    t2_policy_id: Vec<u8>,
+    t2_name: Vec<u8>,
+    t1_decimals: Option<i32>,
+    t2_decimals: Option<i32>,
+    amount1: i64,
+    amount2: i64,
+    output_index: Option<i64>,
+    tx_hash: Vec<u8>,
+    block_hash: Vec<u8>,
+    slot: i64,
+    timestamp: Option<DateTime>,
+}
+
+// Origin of a stored price update.
+fn get_price_origin(
+    tx_hash: &[u8],
+    block_hash: &[u8],
+    slot: i64,
+    script_hash: &[u8],
+    output_index: Option<i64>,
+) -> EventOrigin {
+    let tx_hash = hex::encode(tx_hash);
+    EventOrigin {
+        event_id: utils::get_price_event_id(&tx_hash, &hex::encode(script_hash)),
+        output: output_index.map(|index| OutputReference {
+            tx_hash: tx_hash.clone(),
+            index: index as u64,
+        }),
+        tx_hash,
+        block_hash: hex::encode(block_hash),
+        slot: slot as u64,
+    }
+}
+
+// Origin of a stored swap. Swaps stored without their order are identified by the row id.
+fn get_swap_origin(
+    script_hash: &[u8],
+    position: i64,
+    tx_hash: &[u8],
+    block_hash: &[u8],
+    slot: i64,
+    order_tx_hash: Option<Vec<u8>>,
+    order_index: Option<i64>,
+) -> EventOrigin {
+    let tx_hash = hex::encode(tx_hash);
+    let order = match (order_tx_hash, order_index) {
+        (Some(order_tx_hash), Some(order_index)) => Some(OutputReference {
+            tx_hash: hex::encode(order_tx_hash),
+            index: order_index as u64,
+        }),
+        _ => None,
+    };
+    EventOrigin {
+        event_id: utils::get_swap_event_id(
+            &tx_hash,
+            &hex::encode(script_hash),
+            order.as_ref(),
+            position as u64,
+        ),
+        tx_hash,
+        block_hash: hex::encode(block_hash),
+        slot: slot as u64,
+        output: order,
+    }
+}
+
+// The latest price update of every pool.
+async fn get_latest_price_updates(db: &DatabaseConnection) -> anyhow::Result<Vec<RawPriceUpdate>> {
+    // The raw SQL query here is rather unlucky, but we need to join the token table twice,
+    // and the sea-orm version usde by us (dcSpark's fork which implements
+    // exec_many_with_returning) doesn't seem to support join aliases.
+    // TODO figure out how to do both multi-joining here and exec_many_with_returning above.
+    Ok(
+        RawPriceUpdate::find_by_statement(Statement::from_sql_and_values(
+            // The query is plain SQL understood by both Postgres and SQLite
+            db.get_database_backend(),
+            r#"
+            SELECT
+                script_hash,
+                t1.id AS t1_id,
+                t2.id AS t2_id,
+                t1.policy_id AS t1_policy_id,
+                t1.name AS t1_name,
+                t2.policy_id AS t2_policy_id,
+                t2.name AS t2_name,
+                t1.decimals AS t1_decimals,
+                t2.decimals AS t2_decimals,
+                amount1,
+                amount2,
+                output_index,
+                "transaction".hash AS tx_hash,
+                block.hash AS block_hash,
+                block.slot AS slot,
+                block.timestamp AS timestamp
+
+            FROM price_update
+            JOIN token AS t1 ON t1.id = price_update.token1_id
+            JOIN token AS t2 ON t2.id = price_update.token2_id
+            JOIN "transaction" ON "transaction".id = price_update.tx_id
+            JOIN block ON block.id = "transaction".block_id
+            WHERE (script_hash, token1_id, token2_id, tx_id) IN (
+                SELECT script_hash, token1_id, token2_id, MAX(tx_id)
+                FROM price_update
+                GROUP BY script_hash, token1_id, token2_id
+            )
+            "#,
+            vec![],
+        ))
+        .all(db)
+        .await?,
+    )
+}
+
+pub async fn get_latest_prices(db: &DatabaseConnection) -> anyhow::Result<Vec<ExchangeRate>> {
+    Ok(get_latest_price_updates(db)
+        .await?
+        .iter()
+        .map(|r| {
+            let rate = utils::get_rate(r.amount1, r.amount2);
+            ExchangeRate {
+                script_hash: hex::encode(r.script_hash.clone()),
+                asset1: r.t1_id,
+                asset2: r.t2_id,
+                rate,
+                adjusted_rate: utils::get_adjusted_rate(rate, r.t1_decimals, r.t2_decimals),
+                timestamp: r.timestamp.map(|t| t.timestamp()),
+                origin: get_price_origin(
+                    &r.tx_hash,
+                    &r.block_hash,
+                    r.slot,
+                    &r.script_hash,
+                    r.output_index,
+                ),
+            }
+        })
+        .collect())
+}
+
+/// Current reserves of all pools, i.e. their latest price updates.
+pub async fn get_latest_pool_states(db: &DatabaseConnection) -> anyhow::Result<Vec<PoolState>> {
+    Ok(get_latest_price_updates(db)
+        .await?
+        .into_iter()
+        .map(|r| PoolState {
+            script_hash: hex::encode(r.script_hash),
+            asset1: r.t1_id,
+            asset2: r.t2_id,
+            reserve1: AssetAmount {
+                asset: Asset {
+                    policy_id: hex::encode(r.t1_policy_id),
+                    name: hex::encode(r.t1_name),
+                },
+                amount: r.amount1 as u64,
+            },
+            reserve2: AssetAmount {
+                asset: Asset {
+                    policy_id: hex::encode(r.t2_policy_id),
+                    name: hex::encode(r.t2_name),
+                },
+                amount: r.amount2 as u64,
+            },
+            timestamp: r.timestamp.map(|t| t.timestamp()).unwrap_or_default(),
+        })
+        .collect())
+}
+
+pub async fn get_assets(db: &DatabaseConnection) -> anyhow::Result<HashMap<i64, AssetInfo>> {
+    let tokens = token::Entity::find().all(db).await?;
+    Ok(tokens
+        .iter()
+        .map(|t| {
+            (
+                t.id,
+                AssetInfo {
+                    asset: Asset {
+                        policy_id: hex::encode(t.policy_id.clone()),
+                        name: hex::encode(t.name.clone()),
+                    },
+                    metadata: get_stored_metadata(t),
+                },
+            )
+        })
+        .collect())
+}
+
+fn get_stored_metadata(token: &token::Model) -> TokenMetadata {
+    TokenMetadata {
+        decimals: token.decimals,
+        ticker: token.ticker.clone(),
+        display_name: token.display_name.clone(),
+        logo: token.logo.clone(),
+    }
+}
+
+// Returns whether the row had to be updated.
+async fn set_token_metadata(
+    token: token::Model,
+    metadata: TokenMetadata,
+    db: &DatabaseConnection,
+) -> anyhow::Result<bool> {
+    if get_stored_metadata(&token) == metadata {
+        return Ok(false);
+    }
+    let mut token_model = token.into_active_model();
+    token_model.decimals = Set(metadata.decimals);
+    token_model.ticker = Set(metadata.ticker);
+    token_model.display_name = Set(metadata.display_name);
+    token_model.logo = Set(metadata.logo);
+    token_model.update(db).await?;
+    Ok(true)
+}
+
+/// Fill the missing metadata of the stored tokens from the token registry. Metadata published
+/// on-chain is kept, as it is maintained by the token issuer.
+pub async fn update_token_metadata(db: &DatabaseConnection) -> anyhow::Result<()> {
+    let tokens = token::Entity::find().all(db).await?;
+    let mut updated = 0;
+    for token in tokens.into_iter() {
+        let metadata = match registry::get(&Asset {
+            policy_id: hex::encode(&token.policy_id),
+            name: hex::encode(&token.name),
+        }) {
+            Some(metadata) => get_stored_metadata(&token).merge(metadata),
+            None => continue,
+        };
+        if set_token_metadata(token, metadata, db).await? {
+            updated += 1;
+        }
+    }
+    tracing::info!("Token metadata updated for {} tokens", updated);
+    Ok(())
+}
+
+/// Update the stored token with metadata published on-chain. Tokens which are not stored yet,
+/// i.e. weren't seen in any watched transaction, are ignored.
+pub async fn update_onchain_token_metadata(
+    asset: &Asset,
+    metadata: TokenMetadata,
+    db: &DatabaseConnection,
+) -> anyhow::Result<()> {
+    let token = token::Entity::find()
+        .filter(
+            token::Column::PolicyId
+                .eq(hex::decode(&asset.policy_id)?)
+                .and(token::Column::Name.eq(hex::decode(&asset.name)?)),
+        )
+        .one(db)
+        .await?;
+    if let Some(token) = token {
+        let metadata = metadata.merge(get_stored_metadata(&token));
+        set_token_metadata(token, metadata, db).await?;
+    }
+    Ok(())
+}
+
+/// Requested token pair together with the order it's stored in.
+struct Pair {
+    // Token ids in the canonical order
+    token1_id: i64,
+    token2_id: i64,
+    // The pair was requested in the reverse of the canonical order
+    reversed: bool,
+    // Decimals in the requested order
+    decimals1: Option<i32>,
+    decimals2: Option<i32>,
+}
+
+impl Pair {
+    fn amounts(&self, amount1: i64, amount2: i64) -> (i64, i64) {
+        match self.reversed {
+            true => (amount2, amount1),
+            false => (amount1, amount2),
+        }
+    }
+}
+
+async fn get_pair(asset_id1: i64, asset_id2: i64, db: &DatabaseConnection) -> anyhow::Result<Pair> {
+    let tokens = token::Entity::find()
+        .filter(token::Column::Id.is_in([asset_id1, asset_id2]))
+        .all(db)
+        .await?;
+    let token = |id| tokens.iter().find(|t| t.id == id);
+    let (token1, token2) = (token(asset_id1), token(asset_id2));
+    let reversed = match (token1, token2) {
+        (Some(t1), Some(t2)) => (&t1.policy_id, &t1.name) > (&t2.policy_id, &t2.name),
+        _ => false,
+    };
+    Ok(Pair {
+        token1_id: if reversed { asset_id2 } else { asset_id1 },
+        token2_id: if reversed { asset_id1 } else { asset_id2 },
+        reversed,
+        decimals1: token1.and_then(|t| t.decimals),
+        decimals2: token2.and_then(|t| t.decimals),
+    })
+}
+
+pub async fn get_token_price_history(
+    asset_id1: i64,
+    asset_id2: i64,
+    count: u64,
+    db: &DatabaseConnection,
+) -> anyhow::Result<Vec<ExchangeHistory>> {
+    #[derive(FromQueryResult)]
+    struct QueryPriceResult {
+        script_hash: Vec<u8>,
+        amount1: i64,
+        amount2: i64,
+        tx_id: i64,
+        output_index: Option<i64>,
+        tx_hash: Vec<u8>,
+        block_hash: Vec<u8>,
+        slot: i64,
+        timestamp: Option<DateTime>,
+    }
+
+    let pair = get_pair(asset_id1, asset_id2, db).await?;
+    let data = price_update::Entity::find()
+        .select_only()
+        .column(price_update::Column::ScriptHash)
+        .column(price_update::Column::Amount1)
+        .column(price_update::Column::Amount2)
+        .column(price_update::Column::TxId)
+        .column(price_update::Column::OutputIndex)
+        .column_as(transaction::Column::Hash, "tx_hash")
+        .column_as(block::Column::Hash, "block_hash")
+        .column(block::Column::Slot)
+        .column(block::Column::Timestamp)
+        .join(
+            JoinType::InnerJoin,
+            price_update::Relation::Transaction.def(),
+        )
+        .join(JoinType::InnerJoin, transaction::Relation::Block.def())
+        .filter(price_update::Column::Token1Id.eq(pair.token1_id))
+        .filter(price_update::Column::Token2Id.eq(pair.token2_id))
+        .order_by(price_update::Column::TxId, Order::Desc)
+        .limit(count)
+        .into_model::<QueryPriceResult>()
+        .all(db)
+        .await?;
+
+    Ok(data
+        .iter()
+        .map(|p| {
+            let (amount1, amount2) = pair.amounts(p.amount1, p.amount2);
+            let rate = utils::get_rate(amount1, amount2);
+            ExchangeHistory {
+                script_hash: hex::encode(&p.script_hash),
+                amount1,
+                amount2,
+                rate,
+                adjusted_rate: utils::get_adjusted_rate(rate, pair.decimals1, pair.decimals2),
+                tx_id: p.tx_id,
+                timestamp: p.timestamp.map(|t| t.timestamp()),
+                origin: get_price_origin(
+                    &p.tx_hash,
+                    &p.block_hash,
+                    p.slot,
+                    &p.script_hash,
+                    p.output_index,
+                ),
+            }
+        })
+        .collect())
+}
+
+pub async fn get_swap_history(
+    asset_id1: i64,
+    asset_id2: i64,
+    count: u64,
+    db: &DatabaseConnection,
+) -> anyhow::Result<Vec<SwapHistory>> {
+    #[derive(FromQueryResult)]
+    struct QuerySwapResult {
+        position: i64,
+        script_hash: Vec<u8>,
+        amount1: i64,
+        amount2: i64,
+        tx_id: i64,
+        direction: bool,
+        order_tx_hash: Option<Vec<u8>>,
+        order_index: Option<i64>,
+        min_receive: Option<i64>,
+        deadline: Option<DateTime>,
+        slippage: Option<Decimal>,
+        tx_hash: Vec<u8>,
+        block_hash: Vec<u8>,
+        slot: i64,
+        timestamp: Option<DateTime>,
+    }
+
+    let pair = get_pair(asset_id1, asset_id2, db).await?;
+    let data = swap::Entity::find()
+        .select_only()
+        .column(swap::Column::Position)
+        .column(swap::Column::ScriptHash)
+        .column(swap::Column::Amount1)
+        .column(swap::Column::Amount2)
+        .column(swap::Column::TxId)
+        .column(swap::Column::Direction)
+        .column(swap::Column::OrderTxHash)
+        .column(swap::Column::OrderIndex)
+        .column(swap::Column::MinReceive)
+        .column(swap::Column::Deadline)
+        .column(swap::Column::Slippage)
+        .column_as(transaction::Column::Hash, "tx_hash")
+        .column_as(block::Column::Hash, "block_hash")
+        .column(block::Column::Slot)
+        .column(block::Column::Timestamp)
+        .join(JoinType::InnerJoin, swap::Relation::Transaction.def())
+        .join(JoinType::InnerJoin, transaction::Relation::Block.def())
+        .filter(swap::Column::Token1Id.eq(pair.token1_id))
+        .filter(swap::Column::Token2Id.eq(pair.token2_id))
+        .order_by(swap::Column::TxId, Order::Desc)
+        .limit(count)
+        .order_by(swap::Column::TxId, Order::Asc)
+        .into_model::<QuerySwapResult>()
+        .all(db)
+        .await?;
+
+    Ok(data
+        .into_iter()
+        .map(|p| {
+            let (amount1, amount2) = pair.amounts(p.amount1, p.amount2);
+            // Selling the first token of the pair is buying the second one
+            SwapHistory {
+                script_hash: hex::encode(&p.script_hash),
+                amount1,
+                amount2,
+                tx_id: p.tx_id,
+                direction: match p.direction != pair.reversed {
+                    true => "Sell".to_string(),
+                    false => "Buy".to_string(),
+                },
+                timestamp: p.timestamp.map(|t| t.timestamp()),
+                min_receive: p.min_receive.map(|m| m as u64),
+                deadline: p.deadline.map(|d| d.timestamp()),
+                slippage: p.slippage,
+                origin: get_swap_origin(
+                    &p.script_hash,
+                    p.position,
+                    &p.tx_hash,
+                    &p.block_hash,
+                    p.slot,
+                    p.order_tx_hash,
+                    p.order_index,
+                ),
+            }
+        })
+        .collect())
+}
+
+/// Latest swaps of the trader across all pools. Swaps are matched by the stake credential when
+/// known, so that all addresses of the wallet are included, otherwise by the payment one.
+pub async fn get_trader_swaps(
+    payment_credential: Option<Vec<u8>>,
+    stake_credential: Option<Vec<u8>>,
+    count: u64,
+    db: &DatabaseConnection,
+) -> anyhow::Result<Vec<TraderSwap>> {
+    #[derive(FromQueryResult)]
+    struct QuerySwapResult {
+        position: i64,
+        script_hash: Vec<u8>,
+        token1_id: i64,
+        token2_id: i64,
+        amount1: i64,
+        amount2: i64,
+        direction: bool,
+        payment_credential: Option<Vec<u8>>,
+        order_tx_hash: Option<Vec<u8>>,
+        order_index: Option<i64>,
+        min_receive: Option<i64>,
+        deadline: Option<DateTime>,
+        slippage: Option<Decimal>,
+        tx_hash: Vec<u8>,
+        block_hash: Vec<u8>,
+        slot: i64,
+        timestamp: Option<DateTime>,
+    }
+
+    let condition = match (payment_credential, stake_credential) {
+        (_, Some(stake_credential)) => swap::Column::StakeCredential.eq(stake_credential),
+        (Some(payment_credential), None) => swap::Column::PaymentCredential.eq(payment_credential),
+        (None, None) => return Ok(Vec::new()),
+    };
+    let data = swap::Entity::find()
+        .select_only()
+        .column(swap::Column::Position)
+        .column(swap::Column::ScriptHash)
+        .column(swap::Column::Token1Id)
+        .column(swap::Column::Token2Id)
+        .column(swap::Column::Amount1)
+        .column(swap::Column::Amount2)
+        .column(swap::Column::Direction)
+        .column(swap::Column::PaymentCredential)
+        .column(swap::Column::OrderTxHash)
+        .column(swap::Column::OrderIndex)
+        .column(swap::Column::MinReceive)
+        .column(swap::Column::Deadline)
+        .column(swap::Column::Slippage)
+        .column_as(transaction::Column::Hash, "tx_hash")
+        .column_as(block::Column::Hash, "block_hash")
+        .column(block::Column::Slot)
+        .column(block::Column::Timestamp)
+        .join(JoinType::InnerJoin, swap::Relation::Transaction.def())
+        .join(JoinType::InnerJoin, transaction::Relation::Block.def())
+        .filter(condition)
+        .order_by(swap::Column::TxId, Order::Desc)
+        .order_by(swap::Column::Id, Order::Desc)
+        .limit(count)
+        .into_model::<QuerySwapResult>()
+        .all(db)
+        .await?;
+
+    Ok(data
+        .into_iter()
+        .map(|p| TraderSwap {
+            script_hash: hex::encode(&p.script_hash),
+            asset1: p.token1_id,
+            amount1: p.amount1,
+            asset2: p.token2_id,
+            amount2: p.amount2,
+            direction: match p.direction {
+                true => "Sell".to_string(),
+                false => "Buy".to_string(),
+            },
+            timestamp: p.timestamp.map(|t| t.timestamp()),
+            min_receive: p.min_receive.map(|m| m as u64),
+            deadline: p.deadline.map(|d| d.timestamp()),
+            slippage: p.slippage,
+            payment_credential: p.payment_credential.map(hex::encode),
+            origin: get_swap_origin(
+                &p.script_hash,
+                p.position,
+                &p.tx_hash,
+                &p.block_hash,
+                p.slot,
+                p.order_tx_hash,
+                p.order_index,
+            ),
+        })
+        .collect())
+}
+
+pub async fn add_to_candle(
+    series: &SeriesKey,
+    interval: &str,
+    start: i64,
+    price: Decimal,
+    volume: (i64, i64),
+    trades: i64,
+    db: &DatabaseConnection,
+) -> anyhow::Result<()> {
+    let (script_hash, token1_id, token2_id) = series;
+    let start = DateTime::from_timestamp_opt(start, 0)
+        .ok_or_else(|| anyhow::anyhow!("Invalid candle start"))?;
+    let candle_model = candle::Entity::find()
+        .filter(candle::Column::ScriptHash.eq(script_hash.clone()))
+        .filter(candle::Column::Token1Id.eq(*token1_id))
+        .filter(candle::Column::Token2Id.eq(*token2_id))
+        .filter(candle::Column::Interval.eq(interval))
+        .filter(candle::Column::Start.eq(start))
+        .one(db)
+        .await?;
+
+    match candle_model {
+        Some(c) => {
+            let mut candle_model: candle::ActiveModel = c.clone().into();
+            candle_model.high = Set(c.high.max(price));
+            candle_model.low = Set(c.low.min(price));
+            candle_model.close = Set(price);
+            candle_model.volume1 = Set(c.volume1 + volume.0);
+            candle_model.volume2 = Set(c.volume2 + volume.1);
+            candle_model.trades = Set(c.trades + trades);
+            candle_model.update(db).await?;
+        }
+        None => {
+            let candle_model = candle::ActiveModel {
+                script_hash: Set(script_hash.clone()),
+                token1_id: Set(*token1_id),
+                token2_id: Set(*token2_id),
+                interval: Set(interval.to_string()),
+                start: Set(start),
+                open: Set(price),
+                high: Set(price),
+                low: Set(price),
+                close: Set(price),
+                volume1: Set(volume.0),
+                volume2: Set(volume.1),
+                trades: Set(trades),
+                ..Default::default()
+            };
+            candle_model.insert(db).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Build the candle from scratch from price updates and swaps in [start, end), the candle is
+/// removed if there is no price update in the interval anymore.
+pub async fn recompute_candle(
+    series: &SeriesKey,
+    interval: &str,
+    start: i64,
+    end: i64,
+    db: &DatabaseConnection,
+) -> anyhow::Result<()> {
+    #[derive(FromQueryResult)]
+    struct QueryAmountResult {
+        amount1: i64,
+        amount2: i64,
+    }
+
+    let (script_hash, token1_id, token2_id) = series;
+    let start = DateTime::from_timestamp_opt(start, 0)
+        .ok_or_else(|| anyhow::anyhow!("Invalid candle start"))?;
+    let end = DateTime::from_timestamp_opt(end, 0)
+        .ok_or_else(|| anyhow::anyhow!("Invalid candle end"))?;
+
+    let mut price_condition = Condition::all()
+        .add(price_update::Column::Token1Id.eq(*token1_id))
+        .add(price_update::Column::Token2Id.eq(*token2_id))
+        .add(block::Column::Timestamp.gte(start))
+        .add(block::Column::Timestamp.lt(end));
+    let mut swap_condition = Condition::all()
+        .add(swap::Column::Token1Id.eq(*token1_id))
+        .add(swap::Column::Token2Id.eq(*token2_id))
+        .add(block::Column::Timestamp.gte(start))
+        .add(block::Column::Timestamp.lt(end));
+    // The empty script hash stands for all pools
+    if !script_hash.is_empty() {
+        price_condition =
+            price_condition.add(price_update::Column::ScriptHash.eq(script_hash.clone()));
+        swap_condition = swap_condition.add(swap::Column::ScriptHash.eq(script_hash.clone()));
+    }
+
+    let prices: Vec<Decimal> = price_update::Entity::find()
+        .select_only()
+        .column(price_update::Column::Amount1)
+        .column(price_update::Column::Amount2)
+        .join(
+            JoinType::InnerJoin,
+            price_update::Relation::Transaction.def(),
+        )
+        .join(JoinType::InnerJoin, transaction::Relation::Block.def())
+        .filter(price_condition)
+        .order_by(price_update::Column::TxId, Order::Asc)
+        .order_by(price_update::Column::Id, Order::Asc)
+        .into_model::<QueryAmountResult>()
+        .all(db)
+        .await?
+        .iter()
+        .filter_map(|p| utils::get_rate(p.amount1, p.amount2))
+        .collect();
+
+    let swaps = swap::Entity::find()
+        .select_only()
+        .column(swap::Column::Amount1)
+        .column(swap::Column::Amount2)
+        .join(JoinType::InnerJoin, swap::Relation::Transaction.def())
+        .join(JoinType::InnerJoin, transaction::Relation::Block.def())
+        .filter(swap_condition)
+        .into_model::<QueryAmountResult>()
+        .all(db)
+        .await?;
+    let volume = swaps
+        .iter()
+        .fold((0, 0), |(v1, v2), s| (v1 + s.amount1, v2 + s.amount2));
+
+    let candle_condition = Condition::all()
+        .add(candle::Column::ScriptHash.eq(script_hash.clone()))
+        .add(candle::Column::Token1Id.eq(*token1_id))
+        .add(candle::Column::Token2Id.eq(*token2_id))
+        .add(candle::Column::Interval.eq(interval))
+        .add(candle::Column::Start.eq(start));
+    candle::Entity::delete_many()
+        .filter(candle_condition)
+        .exec(db)
+        .await?;
+
+    let (open, high, low, close) = match (
+        prices.first(),
+        prices.iter().max(),
+        prices.iter().min(),
+        prices.last(),
+    ) {
+        (Some(open), Some(high), Some(low), Some(close)) => (*open, *high, *low, *close),
+        _ => return Ok(()),
+    };
+    let candle_model = candle::ActiveModel {
+        script_hash: Set(script_hash.clone()),
+        token1_id: Set(*token1_id),
+        token2_id: Set(*token2_id),
+        interval: Set(interval.to_string()),
+        start: Set(start),
+        open: Set(open),
+        high: Set(high),
+        low: Set(low),
+        close: Set(close),
+        volume1: Set(volume.0),
+        volume2: Set(volume.1),
+        trades: Set(swaps.len() as i64),
+        ..Default::default()
+    };
+    candle_model.insert(db).await?;
+    Ok(())
+}
+
+/// Candle series and timestamps of the price updates in blocks after the given slot.
+pub async fn get_price_updates_after_slot(
+    slot: &u64,
+    db: &DatabaseConnection,
+) -> anyhow::Result<Vec<(SeriesKey, i64)>> {
+    #[derive(FromQueryResult)]
+    struct QueryPriceResult {
+        script_hash: Vec<u8>,
+        token1_id: i64,
+        token2_id: i64,
+        timestamp: Option<DateTime>,
+    }
+
+    let data = price_update::Entity::find()
+        .select_only()
+        .column(price_update::Column::ScriptHash)
+        .column(price_update::Column::Token1Id)
+        .column(price_update::Column::Token2Id)
+        .column(block::Column::Timestamp)
+        .join(
+            JoinType::InnerJoin,
+            price_update::Relation::Transaction.def(),
+        )
+        .join(JoinType::InnerJoin, transaction::Relation::Block.def())
+        .filter(block::Column::Slot.gt(*slot))
+        .into_model::<QueryPriceResult>()
+        .all(db)
+        .await?;
+
+    Ok(data
+        .into_iter()
+        .filter_map(|p| {
+            p.timestamp
+                .map(|t| ((p.script_hash, p.token1_id, p.token2_id), t.timestamp()))
+        })
+        .collect())
+}
+
+pub async fn get_candles(
+    asset_id1: i64,
+    asset_id2: i64,
+    script_hash: &[u8],
+    interval: &str,
+    from: i64,
+    to: i64,
+    db: &DatabaseConnection,
+) -> anyhow::Result<Vec<Candle>> {
+    let from = DateTime::from_timestamp_opt(from, 0).ok_or_else(|| anyhow::anyhow!("Bad from"))?;
+    let to = DateTime::from_timestamp_opt(to, 0).ok_or_else(|| anyhow::anyhow!("Bad to"))?;
+    let pair = get_pair(asset_id1, asset_id2, db).await?;
+    let data = candle::Entity::find()
+        .filter(candle::Column::ScriptHash.eq(script_hash.to_vec()))
+        .filter(candle::Column::Token1Id.eq(pair.token1_id))
+        .filter(candle::Column::Token2Id.eq(pair.token2_id))
+        .filter(candle::Column::Interval.eq(interval))
+        .filter(candle::Column::Start.gte(from))
+        .filter(candle::Column::Start.lte(to))
+        .order_by(candle::Column::Start, Order::Asc)
+        .all(db)
+        .await?;
+
+    // Prices of the reversed pair are the inverse ones, so the highest becomes the lowest. Zero
+    // prices can't be inverted, such candles are skipped.
+    Ok(data
+        .iter()
+        .filter_map(|c| match pair.reversed {
+            false => Some(Candle {
+                start: c.start.timestamp(),
+                open: c.open,
+                high: c.high,
+                low: c.low,
+                close: c.close,
+                volume1: c.volume1,
+                volume2: c.volume2,
+                trades: c.trades,
+            }),
+            true => Some(Candle {
+                start: c.start.timestamp(),
+                open: utils::get_rate(Decimal::ONE, c.open)?,
+                high: utils::get_rate(Decimal::ONE, c.low)?,
+                low: utils::get_rate(Decimal::ONE, c.high)?,
+                close: utils::get_rate(Decimal::ONE, c.close)?,
+                volume1: c.volume2,
+                volume2: c.volume1,
+                trades: c.trades,
+            }),
+        })
+        .collect())
+}
+
+/// Replace the mean exchange ratios of the day starting at the given timestamp. The ratio is
+/// volume weighted, i.e. the total swapped amount of token1 over the total amount of token2
+/// across all pools.
+pub async fn compute_mean_exchange_ratios(day: i64, db: &DatabaseConnection) -> anyhow::Result<()> {
+    #[derive(FromQueryResult)]
+    struct QuerySwapResult {
+        token1_id: i64,
+        token2_id: i64,
+        amount1: i64,
+        amount2: i64,
+    }
+
+    let date =
+        DateTime::from_timestamp_opt(day, 0).ok_or_else(|| anyhow::anyhow!("Invalid date"))?;
+    let next_date = DateTime::from_timestamp_opt(day + 86_400, 0)
+        .ok_or_else(|| anyhow::anyhow!("Invalid date"))?;
+
+    let swaps = swap::Entity::find()
+        .select_only()
+        .column(swap::Column::Token1Id)
+        .column(swap::Column::Token2Id)
+        .column(swap::Column::Amount1)
+        .column(swap::Column::Amount2)
+        .join(JoinType::InnerJoin, swap::Relation::Transaction.def())
+        .join(JoinType::InnerJoin, transaction::Relation::Block.def())
+        .filter(block::Column::Timestamp.gte(date))
+        .filter(block::Column::Timestamp.lt(next_date))
+        .into_model::<QuerySwapResult>()
+        .all(db)
+        .await?;
+
+    let mut volumes: HashMap<(i64, i64), (i64, i64)> = HashMap::new();
+    for swap in swaps.iter() {
+        let volume = volumes
+            .entry((swap.token1_id, swap.token2_id))
+            .or_insert((0, 0));
+        volume.0 += swap.amount1;
+        volume.1 += swap.amount2;
+    }
+
+    mean_exchange_ratio::Entity::delete_many()
+        .filter(mean_exchange_ratio::Column::Date.eq(date))
+        .exec(db)
+        .await?;
+
+    for ((token1_id, token2_id), (amount1, amount2)) in volumes.into_iter() {
+        let ratio = match utils::get_rate(amount1, amount2) {
+            Some(ratio) => ratio,
+            None => continue,
+        };
+        let mean_exchange_ratio_model = mean_exchange_ratio::ActiveModel {
+            token1: Set(token1_id),
+            token2: Set(token2_id),
+            ratio: Set(ratio),
+            date: Set(date),
+            ..Default::default()
+        };
+        mean_exchange_ratio_model.insert(db).await?;
+    }
+    Ok(())
+}
+
+pub async fn get_last_mean_exchange_ratio_date(
+    db: &DatabaseConnection,
+) -> anyhow::Result<Option<i64>> {
+    Ok(mean_exchange_ratio::Entity::find()
+        .order_by(mean_exchange_ratio::Column::Date, Order::Desc)
+        .one(db)
+        .await?
+        .map(|m| m.date.timestamp()))
+}
+
+pub async fn get_last_block_timestamp(db: &DatabaseConnection) -> anyhow::Result<Option<i64>> {
+    Ok(block::Entity::find()
+        .filter(block::Column::Timestamp.is_not_null())
+        .order_by(block::Column::Slot, Order::Desc)
+        .one(db)
+        .await?
+        .and_then(|b| b.timestamp)
+        .map(|t| t.timestamp()))
+}
+
+/// Hourly volume and trade count of every pool since the given timestamp, read from the hourly
+/// candles.
+pub async fn get_hourly_pool_volumes(
+    since: i64,
+    db: &DatabaseConnection,
+) -> anyhow::Result<Vec<(SeriesKey, i64, PoolVolume)>> {
+    #[derive(FromQueryResult)]
+    struct QueryVolumeResult {
+        script_hash: Vec<u8>,
+        token1_id: i64,
+        token2_id: i64,
+        start: DateTime,
+        volume1: i64,
+        volume2: i64,
+        trades: i64,
+    }
+
+    let since =
+        DateTime::from_timestamp_opt(since, 0).ok_or_else(|| anyhow::anyhow!("Bad since"))?;
+    let data = candle::Entity::find()
+        .select_only()
+        .column(candle::Column::ScriptHash)
+        .column(candle::Column::Token1Id)
+        .column(candle::Column::Token2Id)
+        .column(candle::Column::Start)
+        .column(candle::Column::Volume1)
+        .column(candle::Column::Volume2)
+        .column(candle::Column::Trades)
+        .filter(candle::Column::Interval.eq(Interval::Hour.name()))
+        // The empty script hash stands for all pools of the pair
+        .filter(candle::Column::ScriptHash.ne(Vec::<u8>::new()))
+        .filter(candle::Column::Start.gte(since))
+        .into_model::<QueryVolumeResult>()
+        .all(db)
+        .await?;
+
+    Ok(data
+        .into_iter()
+        .map(|v| {
+            (
+                (v.script_hash, v.token1_id, v.token2_id),
+                v.start.timestamp(),
+                PoolVolume {
+                    volume1: v.volume1,
+                    volume2: v.volume2,
+                    trades: v.trades,
+                    ..Default::default()
+                },
+            )
+        })
+        .collect())
+}
+
+pub async fn get_mean_exchange_ratio_history(
+    asset_id1: i64,
+    asset_id2: i64,
+    from: i64,
+    to: i64,
+    db: &DatabaseConnection,
+) -> anyhow::Result<Vec<MeanExchangeRatio>> {
+    let from = DateTime::from_timestamp_opt(from, 0).ok_or_else(|| anyhow::anyhow!("Bad from"))?;
+    let to = DateTime::from_timestamp_opt(to, 0).ok_or_else(|| anyhow::anyhow!("Bad to"))?;
+    let pair = get_pair(asset_id1, asset_id2, db).await?;
+    let data = mean_exchange_ratio::Entity::find()
+        .filter(mean_exchange_ratio::Column::Token1.eq(pair.token1_id))
+        .filter(mean_exchange_ratio::Column::Token2.eq(pair.token2_id))
+        .filter(mean_exchange_ratio::Column::Date.gte(from))
+        .filter(mean_exchange_ratio::Column::Date.lte(to))
+        .order_by(mean_exchange_ratio::Column::Date, Order::Asc)
+        .all(db)
+        .await?;
+
+    Ok(data
+        .iter()
+        .filter_map(|m| {
+            let ratio = match pair.reversed {
+                true => utils::get_rate(Decimal::ONE, m.ratio)?,
+                false => m.ratio,
+            };
+            Some(MeanExchangeRatio {
+                date: m.date.timestamp(),
+                ratio,
+                adjusted_ratio: utils::get_adjusted_rate(
+                    Some(ratio),
+                    pair.decimals1,
+                    pair.decimals2,
+                ),
+            })
+        })
+        .collect())
+}
+
+#[derive(FromQueryResult)]
+struct QueryOutputResult {
+    id: i64,
+    index: i32,
+    datum_hash: Option<String>,
+    hash: Vec<u8>,
+    payload: String,
+}
+
+// Condition matching the outputs referenced by the inputs, the transaction has to be joined.
+fn get_output_condition(inputs: &[TxInputRecord]) -> anyhow::Result<Condition> {
+    let mut condition = Condition::any();
+    for input in inputs.iter() {
+        condition = condition.add(
+            transaction_output::Column::Index
+                .eq(input.index)
+                .and(transaction::Column::Hash.eq(hex::decode(&input.tx_id)?)),
+        );
+    }
+    Ok(condition)
+}
+
+pub async fn get_utxo_input(
+    inputs: &[TxInputRecord],
+    db: &DatabaseConnection,
+) -> anyhow::Result<Vec<Option<TxOutputRecord>>> {
+    let mut output_query = Vec::new();
+    for chunk in inputs.chunks(QUERY_CHUNK_SIZE) {
+        output_query.extend(
+            transaction_output::Entity::find()
+                .select_only()
+                .column(transaction_output::Column::Id)
+                .column(transaction_output::Column::Index)
+                .column(transaction_output::Column::DatumHash)
+                .column(transaction::Column::Hash)
+                .column(address::Column::Payload)
+                .join(
+                    JoinType::InnerJoin,
+                    transaction_output::Relation::Transaction.def(),
+                )
+                .join(
+                    JoinType::InnerJoin,
+                    transaction_output::Relation::Address.def(),
+                )
+                .filter(get_output_condition(chunk)?)
+                .into_model::<QueryOutputResult>()
+                .all(db)
+                .await?,
+        );
+    }
+
+    let mut outputs = build_output_records(output_query, db).await?;
+
+    Ok(inputs
+        .iter()
+        .map(|input| outputs.remove(&(input.tx_id.clone(), input.index)))
+        .collect())
+}
+
+/// Latest unspent order and pool outputs together with their (transaction hash, index)
+/// reference, newest first.
+pub async fn get_latest_outputs(
+    count: u64,
+    db: &DatabaseConnection,
+) -> anyhow::Result<Vec<((String, u64), TxOutputRecord)>> {
+    // Outputs holding an order or the reserves of a pool, the others are of no interest
+    let watched = Condition::any()
+        .add(Expr::cust(
+            r#"EXISTS (SELECT 1 FROM "order" o WHERE o.tx_id = "transaction_output"."tx_id" AND o.output_index = "transaction_output"."index")"#,
+        ))
+        .add(Expr::cust(
+            r#"EXISTS (SELECT 1 FROM "price_update" p WHERE p.tx_id = "transaction_output"."tx_id" AND p.output_index = "transaction_output"."index")"#,
+        ));
+    let output_query = transaction_output::Entity::find()
+        .select_only()
+        .column(transaction_output::Column::Id)
+        .column(transaction_output::Column::Index)
+        .column(transaction_output::Column::DatumHash)
+        .column(transaction::Column::Hash)
+        .column(address::Column::Payload)
+        .join(
+            JoinType::InnerJoin,
+            transaction_output::Relation::Transaction.def(),
+        )
+        .join(
+            JoinType::InnerJoin,
+            transaction_output::Relation::Address.def(),
+        )
+        .filter(transaction_output::Column::SpentTxId.is_null())
+        .filter(watched)
+        .order_by(transaction_output::Column::Id, Order::Desc)
+        .limit(count)
+        .into_model::<QueryOutputResult>()
+        .all(db)
+        .await?;
+
+    let order: Vec<(String, u64)> = output_query
+        .iter()
+        .map(|o| (hex::encode(&o.hash), o.index as u64))
+        .collect();
+    let mut outputs = build_output_records(output_query, db).await?;
+
+    Ok(order
+        .into_iter()
+        .filter_map(|r| outputs.remove(&r).map(|o| (r, o)))
+        .collect())
+}
+
+// Restore the oura output records, the token transfers are fetched in chunks of outputs.
+async fn build_output_records(
+    output_query: Vec<QueryOutputResult>,
+    db: &DatabaseConnection,
+) -> anyhow::Result<HashMap<(String, u64), TxOutputRecord>> {
+    #[derive(FromQueryResult)]
+    struct QueryTokenResult {
+        output_id: i64,
+        amount: i64,
+        policy_id: Vec<u8>,
+        name: Vec<u8>,
+    }
+
+    if output_query.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let mut token_query = Vec::new();
+    for chunk in output_query.chunks(QUERY_CHUNK_SIZE) {
+        token_query.extend(
+            token_transfer::Entity::find()
+                .select_only()
+                .column(token_transfer::Column::OutputId)
+                .column(token_transfer::Column::Amount)
+                .column(token::Column::PolicyId)
+                .column(token::Column::Name)
+                .join(JoinType::InnerJoin, token_transfer::Relation::Token.def())
+                .filter(token_transfer::Column::OutputId.is_in(chunk.iter().map(|o| o.id)))
+                .into_model::<QueryTokenResult>()
+                .all(db)
+                .await?,
+        );
+    }
+
+    let mut records: HashMap<i64, TxOutputRecord> = output_query
+        .iter()
+        .map(|o| {
+            (
+                o.id,
+                TxOutputRecord {
                     address: o.payload.clone(),
                     amount: 0,
                     assets: None,
                     datum_hash: o.datum_hash.clone(),
-                };
-                let token_query = token_transfer::Entity::find()
-                    .select_only()
-                    .column(token_transfer::Column::Amount)
-                    .column(token::Column::PolicyId)
-                    .column(token::Column::Name)
-                    .join(JoinType::InnerJoin, token_transfer::Relation::Token.def())
-                    .filter(token_transfer::Column::OutputId.eq(o.id))
-                    .into_model::<QueryTokenResult>()
-                    .all(db)
-                    .await?;
-
-                for token in token_query.iter() {
-                    if token.policy_id.is_empty() && token.name.is_empty() {
-                        out.amount = token.amount as u64;
-                    } else {
-                        assets.push(OutputAssetRecord {
-                            policy: hex::encode(&token.policy_id),
-                            asset: hex::encode(&token.name),
-                            asset_ascii: Some(String::from_utf8_lossy(&token.name).to_string()),
-                            amount: token.amount as u64,
-                        })
-                    }
-                }
-                if !assets.is_empty() {
-                    out.assets = Some(assets);
-                }
-                Some(out)
-            }
-            _ => None,
-        });
+                    inline_datum: None,
+                },
+            )
+        })
+        .collect();
+
+    for token in token_query.iter() {
+        let out = match records.get_mut(&token.output_id) {
+            Some(out) => out,
+            None => continue,
+        };
+        if token.policy_id.is_empty() && token.name.is_empty() {
+            out.amount = token.amount as u64;
+        } else {
+            out.assets
+                .get_or_insert_with(Vec::new)
+                .push(OutputAssetRecord {
+                    policy: hex::encode(&token.policy_id),
+                    asset: hex::encode(&token.name),
+                    asset_ascii: Some(String::from_utf8_lossy(&token.name).to_string()),
+                    amount: token.amount as u64,
+                })
+        }
     }
-    Ok(result)
+
+    Ok(output_query
+        .iter()
+        .filter_map(|o| {
+            records
+                .remove(&o.id)
+                .map(|r| ((hex::encode(&o.hash), o.index as u64), r))
+        })
+        .collect())
 }
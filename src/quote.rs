@@ -0,0 +1,192 @@
+use rust_decimal::Decimal;
+
+use crate::{
+    config::PoolConfig,
+    liquidity,
+    types::{PoolQuote, Quote},
+};
+
+// Fees are given in basis points.
+static FEE_DENOMINATOR: u128 = 10_000;
+
+// Iterations limit of the Newton's method solving the stableswap invariant.
+static MAX_ITERATIONS: usize = 255;
+
+// Spot prices are approximated by a trade of this fraction of the input reserve.
+static SPOT_TRADE_FRACTION: u128 = 1_000_000;
+
+/// Pricing function of the pool.
+pub enum Curve {
+    ConstantProduct,
+    StableSwap { amplification: u128 },
+}
+
+impl Curve {
+    pub fn new(pool: &PoolConfig) -> Curve {
+        match pool.amplification {
+            Some(amplification) => Curve::StableSwap {
+                amplification: amplification as u128,
+            },
+            None => Curve::ConstantProduct,
+        }
+    }
+
+    /// Output amount of a trade of the given input amount, fees not included.
+    pub fn get_amount_out(
+        &self,
+        reserve_in: u128,
+        reserve_out: u128,
+        amount_in: u128,
+    ) -> Option<u128> {
+        match self {
+            Curve::ConstantProduct => reserve_out
+                .checked_mul(amount_in)?
+                .checked_div(reserve_in.checked_add(amount_in)?),
+            Curve::StableSwap { amplification } => {
+                let d = get_stableswap_invariant(reserve_in, reserve_out, *amplification)?;
+                let y =
+                    get_stableswap_reserve(reserve_in.checked_add(amount_in)?, d, *amplification)?;
+                // Round down in favour of the pool
+                reserve_out.checked_sub(y)?.checked_sub(1)
+            }
+        }
+    }
+}
+
+/// The invariant D of the two token stableswap, see the Curve whitepaper:
+/// `A * n^n * (x + y) + D = A * D * n^n + D^(n + 1) / (n^n * x * y)`
+fn get_stableswap_invariant(x: u128, y: u128, amplification: u128) -> Option<u128> {
+    let sum = x.checked_add(y)?;
+    if sum == 0 {
+        return Some(0);
+    }
+    let ann = amplification.checked_mul(4)?;
+    let mut d = sum;
+    for _ in 0..MAX_ITERATIONS {
+        // D^(n + 1) / (n^n * x * y) computed step by step to avoid overflows
+        let d_p = d
+            .checked_mul(d)?
+            .checked_div(x.checked_mul(2)?)?
+            .checked_mul(d)?
+            .checked_div(y.checked_mul(2)?)?;
+        let previous = d;
+        d = ann
+            .checked_mul(sum)?
+            .checked_add(d_p.checked_mul(2)?)?
+            .checked_mul(d)?
+            .checked_div(
+                ann.checked_sub(1)?
+                    .checked_mul(d)?
+                    .checked_add(d_p.checked_mul(3)?)?,
+            )?;
+        if d.abs_diff(previous) <= 1 {
+            return Some(d);
+        }
+    }
+    None
+}
+
+/// Reserve of the second token keeping the invariant for the given reserve of the first one.
+fn get_stableswap_reserve(x: u128, d: u128, amplification: u128) -> Option<u128> {
+    let ann = amplification.checked_mul(4)?;
+    let c = d
+        .checked_mul(d)?
+        .checked_div(x.checked_mul(2)?)?
+        .checked_mul(d)?
+        .checked_div(ann.checked_mul(2)?)?;
+    let b = x.checked_add(d.checked_div(ann)?)?;
+    let mut y = d;
+    for _ in 0..MAX_ITERATIONS {
+        let previous = y;
+        y = y
+            .checked_mul(y)?
+            .checked_add(c)?
+            .checked_div(y.checked_mul(2)?.checked_add(b)?.checked_sub(d)?)?;
+        if y.abs_diff(previous) <= 1 {
+            return Some(y);
+        }
+    }
+    None
+}
+
+/// Simulate the swap of the amount on the pool with the given reserves, pool fee included.
+pub fn quote_pool(
+    pool: &PoolConfig,
+    reserve_in: u64,
+    reserve_out: u64,
+    amount_in: u64,
+) -> Option<PoolQuote> {
+    let fees = pool.as_trait().fees();
+    let curve = Curve::new(pool);
+    let (reserve_in, reserve_out) = (reserve_in as u128, reserve_out as u128);
+
+    let fee = (amount_in as u128)
+        .checked_mul(fees.swap_fee as u128)?
+        .checked_div(FEE_DENOMINATOR)?;
+    let amount_out = curve.get_amount_out(reserve_in, reserve_out, amount_in as u128 - fee)?;
+
+    // Price impact is the relative difference between the spot price and the price of the
+    // trade, fees excluded
+    let spot_in = (reserve_in / SPOT_TRADE_FRACTION).max(1);
+    let spot_out = curve.get_amount_out(reserve_in, reserve_out, spot_in)?;
+    let price_impact = curve
+        .get_amount_out(reserve_in, reserve_out, amount_in as u128)
+        .and_then(|out| {
+            let price = Decimal::from(out as u64).checked_div(Decimal::from(amount_in))?;
+            let spot = Decimal::from(spot_out as u64).checked_div(Decimal::from(spot_in as u64))?;
+            Some(Decimal::ONE - price.checked_div(spot)?)
+        });
+
+    Some(PoolQuote {
+        script_hash: pool.script_hash.clone(),
+        amount_out: amount_out as u64,
+        fee: fee as u64,
+        batcher_fee: fees.ada_swap_in - fees.ada_swap_out,
+        deposit: fees.ada_swap_out,
+        price_impact,
+    })
+}
+
+/// Simulate the swap of the amount of the first token to the second one on every pool of the
+/// pair. The best pool gives the most tokens, the batcher fee is included when swapping to ADA.
+pub fn get_quote(from: i64, to: i64, amount: u64, pools: &[PoolConfig]) -> Quote {
+    let mut quotes = Vec::new();
+    let mut to_ada = false;
+    for state in liquidity::get_pool_states().iter() {
+        let (reserve_in, reserve_out) = match (state.asset1, state.asset2) {
+            (asset1, asset2) if (asset1, asset2) == (from, to) => {
+                (&state.reserve1, &state.reserve2)
+            }
+            (asset1, asset2) if (asset1, asset2) == (to, from) => {
+                (&state.reserve2, &state.reserve1)
+            }
+            _ => continue,
+        };
+        to_ada = reserve_out.asset.policy_id.is_empty() && reserve_out.asset.name.is_empty();
+        let pool = match pools
+            .iter()
+            .find(|p| p.enable && p.script_hash == state.script_hash)
+        {
+            Some(pool) => pool,
+            None => continue,
+        };
+        if let Some(quote) = quote_pool(pool, reserve_in.amount, reserve_out.amount, amount) {
+            quotes.push(quote);
+        }
+    }
+
+    let best = quotes
+        .iter()
+        .max_by_key(|q| match to_ada {
+            true => q.amount_out.saturating_sub(q.batcher_fee),
+            false => q.amount_out,
+        })
+        .map(|q| q.script_hash.clone());
+    Quote {
+        from,
+        to,
+        amount,
+        pools: quotes,
+        best,
+    }
+}
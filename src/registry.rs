@@ -0,0 +1,84 @@
+use std::{collections::HashMap, fs, path::Path, sync::RwLock};
+
+use crate::types::{Asset, TokenMetadata};
+
+// Length of the hex encoded policy id at the start of the registry subject.
+static POLICY_ID_LENGTH: usize = 56;
+
+// Token metadata keyed by the hex encoded policy id and asset name.
+static TOKEN_METADATA: RwLock<Option<HashMap<(String, String), TokenMetadata>>> = RwLock::new(None);
+
+/// Load the Cardano token registry, either a snapshot file or a directory of the registry
+/// `mappings/*.json` files. The snapshot is a JSON array of the registry entries or the response
+/// of the metadata server batch query (`{"subjects": [...]}`).
+pub fn load(path: &str) -> anyhow::Result<()> {
+    let mut entries = Vec::new();
+    if Path::new(path).is_dir() {
+        for file in fs::read_dir(path)? {
+            let file = file?.path();
+            if file.extension().and_then(|e| e.to_str()) == Some("json") {
+                entries.push(serde_json::from_str(&fs::read_to_string(&file)?)?);
+            }
+        }
+    } else {
+        let snapshot: serde_json::Value = serde_json::from_str(&fs::read_to_string(path)?)?;
+        match snapshot {
+            serde_json::Value::Array(array) => entries.extend(array),
+            serde_json::Value::Object(mut object) if object.contains_key("subjects") => {
+                if let serde_json::Value::Array(array) = object["subjects"].take() {
+                    entries.extend(array);
+                }
+            }
+            entry => entries.push(entry),
+        }
+    }
+
+    let mut metadata = HashMap::new();
+    for entry in entries.iter() {
+        match parse_entry(entry) {
+            Some((key, value)) => {
+                metadata.insert(key, value);
+            }
+            None => tracing::debug!("Skipping token registry entry {}", entry["subject"]),
+        }
+    }
+    tracing::info!("Token registry loaded with {} tokens", metadata.len());
+    *TOKEN_METADATA.write().unwrap() = Some(metadata);
+    Ok(())
+}
+
+fn parse_entry(entry: &serde_json::Value) -> Option<((String, String), TokenMetadata)> {
+    let subject = entry["subject"].as_str()?.to_lowercase();
+    if subject.len() < POLICY_ID_LENGTH || hex::decode(&subject).is_err() {
+        return None;
+    }
+    let (policy_id, name) = subject.split_at(POLICY_ID_LENGTH);
+    Some((
+        (policy_id.to_string(), name.to_string()),
+        TokenMetadata {
+            decimals: entry["decimals"]["value"].as_i64().map(|d| d as i32),
+            ticker: entry["ticker"]["value"].as_str().map(String::from),
+            display_name: entry["name"]["value"].as_str().map(String::from),
+            logo: entry["logo"]["value"].as_str().map(String::from),
+        },
+    ))
+}
+
+/// Metadata of the token from the registry. ADA is not a native token, so it's not part of the
+/// registry and it's described here.
+pub fn get(asset: &Asset) -> Option<TokenMetadata> {
+    if asset.policy_id.is_empty() && asset.name.is_empty() {
+        return Some(TokenMetadata {
+            decimals: Some(6),
+            ticker: Some(String::from("ADA")),
+            display_name: Some(String::from("Cardano")),
+            logo: None,
+        });
+    }
+    TOKEN_METADATA
+        .read()
+        .unwrap()
+        .as_ref()?
+        .get(&(asset.policy_id.clone(), asset.name.clone()))
+        .cloned()
+}
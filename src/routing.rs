@@ -0,0 +1,153 @@
+use std::collections::HashMap;
+
+use rust_decimal::Decimal;
+
+use crate::{
+    liquidity, registry,
+    types::{AggregatedPrice, Asset, RouteHop, RoutedPrice},
+    utils,
+};
+
+// Longest route searched, longer routes rarely have any liquidity left.
+static MAX_HOPS: usize = 3;
+
+/// Price graph of the current aggregated pair prices, the pairs are the edges between tokens.
+pub struct PriceGraph {
+    assets: HashMap<i64, Asset>,
+    edges: HashMap<i64, Vec<AggregatedPrice>>,
+}
+
+// Partial route while searching the graph.
+struct Route<'a> {
+    tokens: Vec<i64>,
+    hops: Vec<&'a AggregatedPrice>,
+    // Price of the starting token in units of the last one
+    price: Decimal,
+    // The smallest reserve along the route in units of the starting token
+    liquidity: Option<Decimal>,
+}
+
+impl PriceGraph {
+    /// Build the graph from the aggregated prices known at the moment.
+    pub fn current() -> Self {
+        let mut assets = HashMap::new();
+        for state in liquidity::get_pool_states().into_iter() {
+            assets.insert(state.asset1, state.reserve1.asset);
+            assets.insert(state.asset2, state.reserve2.asset);
+        }
+        let mut edges: HashMap<i64, Vec<AggregatedPrice>> = HashMap::new();
+        for price in liquidity::get_aggregated_prices().into_iter() {
+            if price.rate.is_none() {
+                continue;
+            }
+            edges.entry(price.asset1).or_default().push(price.clone());
+            edges.entry(price.asset2).or_default().push(price);
+        }
+        PriceGraph { assets, edges }
+    }
+
+    pub fn get_id(&self, asset: &Asset) -> Option<i64> {
+        self.assets
+            .iter()
+            .find(|(_, a)| a.policy_id == asset.policy_id && a.name == asset.name)
+            .map(|(id, _)| *id)
+    }
+
+    /// Price of the asset in units of the quote token over the route with the most liquidity,
+    /// i.e. with the largest smallest reserve along the route.
+    pub fn get_price(&self, asset: i64, quote: i64) -> Option<RoutedPrice> {
+        let mut best: Option<Route> = None;
+        let mut stack = vec![Route {
+            tokens: vec![asset],
+            hops: Vec::new(),
+            price: Decimal::ONE,
+            liquidity: None,
+        }];
+        while let Some(route) = stack.pop() {
+            let current = *route.tokens.last().unwrap();
+            if current == quote && !route.hops.is_empty() {
+                let better = match &best {
+                    Some(best) => route.liquidity > best.liquidity,
+                    None => true,
+                };
+                if better {
+                    best = Some(route);
+                }
+                continue;
+            }
+            if route.hops.len() == MAX_HOPS {
+                continue;
+            }
+            for edge in self.edges.get(&current).into_iter().flatten() {
+                if let Some(next) = self.extend(&route, edge) {
+                    stack.push(next);
+                }
+            }
+        }
+
+        let route = best?;
+        let price = route.price;
+        Some(RoutedPrice {
+            asset,
+            quote,
+            price,
+            adjusted_price: utils::get_adjusted_rate(
+                Some(price),
+                self.get_decimals(quote),
+                self.get_decimals(asset),
+            ),
+            liquidity: route.liquidity?,
+            route: route
+                .tokens
+                .windows(2)
+                .zip(route.hops.iter())
+                .map(|(tokens, edge)| RouteHop {
+                    from: tokens[0],
+                    to: tokens[1],
+                    rate: edge.rate,
+                    pools: edge.pools.clone(),
+                })
+                .collect(),
+        })
+    }
+
+    /// USD price of the asset through the configured stablecoin with the most liquid route.
+    pub fn get_usd_price(&self, asset: i64, stablecoins: &[Asset]) -> Option<RoutedPrice> {
+        stablecoins
+            .iter()
+            .filter_map(|stablecoin| self.get_price(asset, self.get_id(stablecoin)?))
+            .max_by(|a, b| a.liquidity.cmp(&b.liquidity))
+    }
+
+    fn extend<'a>(&self, route: &Route<'a>, edge: &'a AggregatedPrice) -> Option<Route<'a>> {
+        let current = *route.tokens.last()?;
+        // Price of the second token in units of the first one
+        let rate = edge.rate?;
+        let (next, reserve, hop_price) = match edge.asset1 == current {
+            true => (
+                edge.asset2,
+                edge.reserve1,
+                utils::get_rate(Decimal::ONE, rate)?,
+            ),
+            false => (edge.asset1, edge.reserve2, rate),
+        };
+        if route.tokens.contains(&next) {
+            return None;
+        }
+        let depth = Decimal::from(reserve).checked_div(route.price)?;
+        let mut tokens = route.tokens.clone();
+        tokens.push(next);
+        let mut hops = route.hops.clone();
+        hops.push(edge);
+        Some(Route {
+            tokens,
+            hops,
+            price: route.price.checked_mul(hop_price)?,
+            liquidity: Some(route.liquidity.map_or(depth, |l| l.min(depth))),
+        })
+    }
+
+    fn get_decimals(&self, id: i64) -> Option<i32> {
+        registry::get(self.assets.get(&id)?)?.decimals
+    }
+}
@@ -7,7 +7,10 @@ use hyper::{
     Body, HeaderMap, Method, Request, Response, StatusCode,
 };
 use sea_orm::Database;
-use std::sync::RwLock;
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
 use tokio::sync::broadcast;
 use tokio_tungstenite::{tungstenite::protocol, WebSocketStream};
 
@@ -23,7 +26,15 @@ pub fn ws_broadcast(msg: &BroadcastMessage) {
         .ok();
 }
 
-use crate::queries;
+use crate::{
+    cache, candles,
+    config::Config,
+    liquidity, orderbook, queries, quote,
+    routing::PriceGraph,
+    stats,
+    types::{AddressSwaps, AlertKind, Asset, OrderStatus},
+    utils,
+};
 
 async fn get_exchange_rates(db_path: String) -> anyhow::Result<String> {
     let db = Database::connect(db_path).await?;
@@ -39,14 +50,19 @@ fn header_matches<S: AsHeaderName>(headers: &HeaderMap<HeaderValue>, name: S, va
         .unwrap_or(false)
 }
 
+// Websocket of the upgraded connection, the error is logged when the upgrade fails.
+type UpgradedSocket = Result<WebSocketStream<hyper::upgrade::Upgraded>, ()>;
+
+/// Switching protocols response with the upgraded socket, or the bad request response boxed as
+/// the error.
 pub fn upgrade_connection(
     req: Request<Body>,
 ) -> Result<
     (
         Response<String>,
-        impl Future<Output = Result<WebSocketStream<hyper::upgrade::Upgraded>, ()>> + Send,
+        impl Future<Output = UpgradedSocket> + Send,
     ),
-    Response<String>,
+    Box<Response<String>>,
 > {
     let mut res = Response::new(String::new());
     let mut header_error = false;
@@ -76,7 +92,7 @@ pub fn upgrade_connection(
 
     if header_error {
         *res.status_mut() = StatusCode::BAD_REQUEST;
-        return Err(res);
+        return Err(Box::new(res));
     }
 
     *res.status_mut() = StatusCode::SWITCHING_PROTOCOLS;
@@ -96,7 +112,7 @@ pub fn upgrade_connection(
 
 fn handle_ws_connection(req: Request<Body>) -> hyper::http::Result<Response<String>> {
     let res = match upgrade_connection(req) {
-        Err(res) => res,
+        Err(res) => *res,
         Ok((res, ws)) => {
             let run_ws_task = async {
                 match ws.await {
@@ -128,12 +144,99 @@ fn handle_ws_connection(req: Request<Body>) -> hyper::http::Result<Response<Stri
     };
     Ok(res)
 }
-async fn get_assets(db_path: String) -> anyhow::Result<String> {
-    let db = Database::connect(db_path).await?;
-    let data = queries::get_assets(&db).await?;
+async fn get_assets(db_path: Option<String>) -> anyhow::Result<String> {
+    let data = match db_path {
+        Some(db_path) => {
+            let db = Database::connect(db_path).await?;
+            // Without the persistent mode the tokens not stored yet are only known in memory
+            let mut assets = queries::get_assets(&db).await?;
+            assets.extend(cache::get_memory_assets(true));
+            assets
+        }
+        // In the stateless mode the ids of the websocket messages are assigned in memory
+        None => cache::get_memory_assets(false),
+    };
+    Ok(serde_json::to_string(&data)?)
+}
+
+fn get_aggregated_prices() -> anyhow::Result<String> {
+    Ok(serde_json::to_string(&liquidity::get_aggregated_prices())?)
+}
+
+fn get_price(path: &str, query: Option<&str>, config: &Config) -> anyhow::Result<String> {
+    let query = parse_query(query)?;
+    let path: Vec<&str> = path.split('/').collect();
+    if path.len() != 3 {
+        return Err(anyhow::anyhow!("Bad path"));
+    }
+    let asset = path[2].parse::<i64>()?;
+    let graph = PriceGraph::current();
+    // The price is null when there is no route between the tokens
+    let price = match query.get("quote").copied().unwrap_or("usd") {
+        "usd" => graph.get_usd_price(asset, &config.stablecoins),
+        "ada" => {
+            let ada = Asset {
+                policy_id: String::new(),
+                name: String::new(),
+            };
+            graph
+                .get_id(&ada)
+                .and_then(|quote| graph.get_price(asset, quote))
+        }
+        quote => graph.get_price(asset, quote.parse::<i64>()?),
+    };
+    Ok(serde_json::to_string(&price)?)
+}
+
+fn get_order_book(path: &str, query: Option<&str>) -> anyhow::Result<String> {
+    let query = parse_query(query)?;
+    let path: Vec<&str> = path.split('/').collect();
+    if path.len() != 4 {
+        return Err(anyhow::anyhow!("Bad path"));
+    }
+    let asset1 = path[2].parse::<i64>()?;
+    let asset2 = path[3].parse::<i64>()?;
+    let precision = match query.get("precision") {
+        Some(precision) => Some(precision.parse::<u32>()?),
+        None => None,
+    };
+    let depths = orderbook::get_depths(asset1, asset2, query.get("pool").copied(), precision);
+    Ok(serde_json::to_string(&depths)?)
+}
+
+fn get_quote(query: Option<&str>, config: &Config) -> anyhow::Result<String> {
+    let query = parse_query(query)?;
+    let from = query
+        .get("from")
+        .ok_or_else(|| anyhow::anyhow!("Bad query"))?
+        .parse::<i64>()?;
+    let to = query
+        .get("to")
+        .ok_or_else(|| anyhow::anyhow!("Bad query"))?
+        .parse::<i64>()?;
+    let amount = query
+        .get("amount")
+        .ok_or_else(|| anyhow::anyhow!("Bad query"))?
+        .parse::<u64>()?;
+    let data = quote::get_quote(from, to, amount, &config.pools);
     Ok(serde_json::to_string(&data)?)
 }
 
+fn get_pool_stats(config: &Config) -> anyhow::Result<String> {
+    Ok(serde_json::to_string(&stats::get_pool_stats(config))?)
+}
+
+fn get_token_stats(path: &str, config: &Config) -> anyhow::Result<String> {
+    let path: Vec<&str> = path.split('/').collect();
+    if path.len() != 4 || path[3] != "stats" {
+        return Err(anyhow::anyhow!("Bad path"));
+    }
+    let asset = path[2].parse::<i64>()?;
+    Ok(serde_json::to_string(&stats::get_token_stats(
+        asset, config,
+    ))?)
+}
+
 async fn get_mean_history(
     path: &str,
     query: Option<&str>,
@@ -186,24 +289,269 @@ async fn get_swap_history(
     Ok(serde_json::to_string(&data)?)
 }
 
-pub async fn route(req: Request<Body>, db_path: String) -> anyhow::Result<Response<String>> {
-    let response = match (req.method(), req.uri().path()) {
-        (&Method::GET, "/health") => Response::builder()
+async fn get_address_swaps(
+    path: &str,
+    query: Option<&str>,
+    db_path: String,
+) -> anyhow::Result<String> {
+    let query = parse_query(query)?;
+    let count = match query.get("count") {
+        Some(count) => count.parse::<u64>()?,
+        None => 10,
+    };
+    let path: Vec<&str> = path.split('/').collect();
+    if path.len() != 4 || path[3] != "swaps" {
+        return Err(anyhow::anyhow!("Bad path"));
+    }
+    // Either a payment or a stake address of the wallet
+    let (payment_credential, stake_credential) =
+        utils::get_credentials(path[2]).ok_or_else(|| anyhow::anyhow!("Bad address"))?;
+    let db = Database::connect(db_path).await?;
+    let swaps = queries::get_trader_swaps(
+        payment_credential.clone(),
+        stake_credential.clone(),
+        count,
+        &db,
+    )
+    .await?;
+    let data = AddressSwaps {
+        payment_credential: payment_credential.map(hex::encode),
+        stake_credential: stake_credential.map(hex::encode),
+        swaps,
+    };
+    Ok(serde_json::to_string(&data)?)
+}
+
+async fn get_orders(query: Option<&str>, db_path: String) -> anyhow::Result<String> {
+    let query = parse_query(query)?;
+    let status = query.get("status").copied().unwrap_or("open");
+    let status = OrderStatus::from_name(status).ok_or_else(|| anyhow::anyhow!("Bad status"))?;
+    let count = match query.get("count") {
+        Some(count) => count.parse::<u64>()?,
+        None => 100,
+    };
+    let script_hash = match query.get("pool") {
+        Some(pool) => Some(hex::decode(pool)?),
+        None => None,
+    };
+    let db = Database::connect(db_path).await?;
+    let data = queries::get_orders(script_hash, status, count, &db).await?;
+    Ok(serde_json::to_string(&data)?)
+}
+
+async fn get_batches(query: Option<&str>, db_path: String) -> anyhow::Result<String> {
+    let query = parse_query(query)?;
+    let count = match query.get("count") {
+        Some(count) => count.parse::<u64>()?,
+        None => 100,
+    };
+    let script_hash = match query.get("pool") {
+        Some(pool) => Some(hex::decode(pool)?),
+        None => None,
+    };
+    let batcher = match query.get("batcher") {
+        Some(batcher) => Some(hex::decode(batcher)?),
+        None => None,
+    };
+    let db = Database::connect(db_path).await?;
+    let data = queries::get_batches(script_hash, batcher, count, &db).await?;
+    Ok(serde_json::to_string(&data)?)
+}
+
+async fn get_batchers(query: Option<&str>, db_path: String) -> anyhow::Result<String> {
+    let query = parse_query(query)?;
+    let script_hash = match query.get("pool") {
+        Some(pool) => Some(hex::decode(pool)?),
+        None => None,
+    };
+    let db = Database::connect(db_path).await?;
+    let data = queries::get_batcher_stats(script_hash, &db).await?;
+    Ok(serde_json::to_string(&data)?)
+}
+
+async fn get_alerts(query: Option<&str>, db_path: String) -> anyhow::Result<String> {
+    let query = parse_query(query)?;
+    let kind = match query.get("kind") {
+        Some(kind) => Some(AlertKind::from_name(kind).ok_or_else(|| anyhow::anyhow!("Bad kind"))?),
+        None => None,
+    };
+    let count = match query.get("count") {
+        Some(count) => count.parse::<u64>()?,
+        None => 100,
+    };
+    let script_hash = match query.get("pool") {
+        Some(pool) => Some(hex::decode(pool)?),
+        None => None,
+    };
+    let db = Database::connect(db_path).await?;
+    let data = queries::get_alerts(kind, script_hash, count, &db).await?;
+    Ok(serde_json::to_string(&data)?)
+}
+
+async fn get_arbitrage(path: &str, query: Option<&str>, db_path: String) -> anyhow::Result<String> {
+    let query = parse_query(query)?;
+    let count = match query.get("count") {
+        Some(count) => count.parse::<u64>()?,
+        None => 100,
+    };
+    // Without the pair the opportunities of all pairs are returned
+    let path: Vec<&str> = path.split('/').collect();
+    let pair = match path.len() {
+        2 => None,
+        4 => Some((path[2].parse::<i64>()?, path[3].parse::<i64>()?)),
+        _ => return Err(anyhow::anyhow!("Bad path")),
+    };
+    let db = Database::connect(db_path).await?;
+    let data = queries::get_arbitrage_opportunities(pair, count, &db).await?;
+    Ok(serde_json::to_string(&data)?)
+}
+
+fn parse_query(query: Option<&str>) -> anyhow::Result<HashMap<&str, &str>> {
+    query
+        .unwrap_or_default()
+        .split('&')
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            part.split_once('=')
+                .ok_or_else(|| anyhow::anyhow!("Bad query"))
+        })
+        .collect()
+}
+
+async fn get_candles(path: &str, query: Option<&str>, db_path: String) -> anyhow::Result<String> {
+    let query = parse_query(query)?;
+    let interval = query.get("interval").copied().unwrap_or("1h");
+    if candles::Interval::from_name(interval).is_none() {
+        return Err(anyhow::anyhow!("Bad interval"));
+    }
+    let from = match query.get("from") {
+        Some(from) => from.parse::<i64>()?,
+        None => 0,
+    };
+    let to = match query.get("to") {
+        Some(to) => to.parse::<i64>()?,
+        None => i32::MAX as i64,
+    };
+    // Without the pool the candles aggregated over all pools of the pair are returned
+    let script_hash = match query.get("pool") {
+        Some(pool) => hex::decode(pool)?,
+        None => Vec::new(),
+    };
+    let path: Vec<&str> = path.split('/').collect();
+    if path.len() != 4 {
+        return Err(anyhow::anyhow!("Bad path"));
+    }
+    let asset_id1 = path[2].parse::<i64>()?;
+    let asset_id2 = path[3].parse::<i64>()?;
+    let db = Database::connect(db_path).await?;
+    let data =
+        queries::get_candles(asset_id1, asset_id2, &script_hash, interval, from, to, &db).await?;
+    Ok(serde_json::to_string(&data)?)
+}
+
+async fn get_mean_exchange_ratio_history(
+    path: &str,
+    query: Option<&str>,
+    db_path: String,
+) -> anyhow::Result<String> {
+    let query = parse_query(query)?;
+    let from = match query.get("from") {
+        Some(from) => from.parse::<i64>()?,
+        None => 0,
+    };
+    let to = match query.get("to") {
+        Some(to) => to.parse::<i64>()?,
+        None => i32::MAX as i64,
+    };
+    let path: Vec<&str> = path.split('/').collect();
+    if path.len() != 4 {
+        return Err(anyhow::anyhow!("Bad path"));
+    }
+    let asset_id1 = path[2].parse::<i64>()?;
+    let asset_id2 = path[3].parse::<i64>()?;
+    let db = Database::connect(db_path).await?;
+    let data =
+        queries::get_mean_exchange_ratio_history(asset_id1, asset_id2, from, to, &db).await?;
+    Ok(serde_json::to_string(&data)?)
+}
+
+pub async fn route(
+    req: Request<Body>,
+    db_path: Option<String>,
+    config: Arc<Config>,
+) -> anyhow::Result<Response<String>> {
+    let response = match (req.method(), req.uri().path(), db_path) {
+        (&Method::GET, "/health", _) => Response::builder()
             .header("Content-Type", "application/json")
             .body(String::from("true")),
-        (&Method::GET, "/exchange_rates") => Response::builder()
+        (&Method::GET, "/socket", _) => handle_ws_connection(req),
+        (&Method::GET, "/assets", db_path) => Response::builder()
+            .header("Content-Type", "application/json")
+            .body(get_assets(db_path).await?),
+        (&Method::GET, "/aggregated_prices", _) => Response::builder()
+            .header("Content-Type", "application/json")
+            .body(get_aggregated_prices()?),
+        (&Method::GET, path, _) if path.starts_with("/price/") => Response::builder()
+            .header("Content-Type", "application/json")
+            .body(get_price(path, req.uri().query(), &config)?),
+        (&Method::GET, path, _) if path.starts_with("/orderbook/") => Response::builder()
+            .header("Content-Type", "application/json")
+            .body(get_order_book(path, req.uri().query())?),
+        (&Method::GET, "/quote", _) => Response::builder()
+            .header("Content-Type", "application/json")
+            .body(get_quote(req.uri().query(), &config)?),
+        (&Method::GET, "/pools", _) => Response::builder()
+            .header("Content-Type", "application/json")
+            .body(get_pool_stats(&config)?),
+        (&Method::GET, path, _) if path.starts_with("/tokens/") => Response::builder()
+            .header("Content-Type", "application/json")
+            .body(get_token_stats(path, &config)?),
+        (&Method::GET, "/exchange_rates", Some(db_path)) => Response::builder()
             .header("Content-Type", "application/json")
             .body(get_exchange_rates(db_path).await?),
-        (&Method::GET, "/socket") => handle_ws_connection(req),
-        (&Method::GET, "/assets") => Response::builder()
+        (&Method::GET, "/batches", Some(db_path)) => Response::builder()
             .header("Content-Type", "application/json")
-            .body(get_assets(db_path).await?),
-        (&Method::GET, path) if path.starts_with("/mean_history/") => Response::builder()
+            .body(get_batches(req.uri().query(), db_path).await?),
+        (&Method::GET, "/batchers", Some(db_path)) => Response::builder()
+            .header("Content-Type", "application/json")
+            .body(get_batchers(req.uri().query(), db_path).await?),
+        (&Method::GET, "/alerts", Some(db_path)) => Response::builder()
+            .header("Content-Type", "application/json")
+            .body(get_alerts(req.uri().query(), db_path).await?),
+        (&Method::GET, path, Some(db_path))
+            if path == "/arbitrage" || path.starts_with("/arbitrage/") =>
+        {
+            Response::builder()
+                .header("Content-Type", "application/json")
+                .body(get_arbitrage(path, req.uri().query(), db_path).await?)
+        }
+        (&Method::GET, "/orders", Some(db_path)) => Response::builder()
             .header("Content-Type", "application/json")
-            .body(get_mean_history(path, req.uri().query(), db_path).await?),
-        (&Method::GET, path) if path.starts_with("/asset_swap/") => Response::builder()
+            .body(get_orders(req.uri().query(), db_path).await?),
+        (&Method::GET, path, Some(db_path)) if path.starts_with("/mean_history/") => {
+            Response::builder()
+                .header("Content-Type", "application/json")
+                .body(get_mean_history(path, req.uri().query(), db_path).await?)
+        }
+        (&Method::GET, path, Some(db_path)) if path.starts_with("/asset_swap/") => {
+            Response::builder()
+                .header("Content-Type", "application/json")
+                .body(get_swap_history(path, req.uri().query(), db_path).await?)
+        }
+        (&Method::GET, path, Some(db_path)) if path.starts_with("/address/") => Response::builder()
             .header("Content-Type", "application/json")
-            .body(get_swap_history(path, req.uri().query(), db_path).await?),
+            .body(get_address_swaps(path, req.uri().query(), db_path).await?),
+        (&Method::GET, path, Some(db_path)) if path.starts_with("/candles/") => Response::builder()
+            .header("Content-Type", "application/json")
+            .body(get_candles(path, req.uri().query(), db_path).await?),
+        (&Method::GET, path, Some(db_path)) if path.starts_with("/mean_exchange_ratio/") => {
+            Response::builder()
+                .header("Content-Type", "application/json")
+                .body(get_mean_exchange_ratio_history(path, req.uri().query(), db_path).await?)
+        }
+        (&Method::GET, _, None) => Response::builder()
+            .status(StatusCode::SERVICE_UNAVAILABLE)
+            .body(String::from("503 Database not configured")),
         _ => Response::builder()
             .status(StatusCode::NOT_FOUND)
             .body(String::from("404 Not found")),
@@ -8,14 +8,21 @@ use oura::{
 };
 use std::{str::FromStr, sync::Arc, thread::JoinHandle};
 
+pub fn get_magic() -> MagicArg {
+    MagicArg::from_str("mainnet").unwrap()
+}
+
+pub fn get_well_known_info() -> anyhow::Result<ChainWellKnownInfo> {
+    ChainWellKnownInfo::try_from_magic(*get_magic())
+        .map_err(|_| anyhow!("chain well known info failed"))
+}
+
 pub fn oura_bootstrap(
     start_block: Option<String>,
     socket: String,
+    well_known: ChainWellKnownInfo,
 ) -> anyhow::Result<(Vec<JoinHandle<()>>, StageReceiver)> {
-    let magic = MagicArg::from_str("mainnet").unwrap();
-
-    let well_known = ChainWellKnownInfo::try_from_magic(*magic)
-        .map_err(|_| anyhow!("chain well known info failed"))?;
+    let magic = get_magic();
 
     let utils = Arc::new(Utils::new(well_known));
 
@@ -1,37 +1,120 @@
 use crate::{
+    cache::UtxoCache,
     config::PoolConfig,
-    types::{AssetAmount, Swap},
+    types::{Asset, AssetAmount, OutputReference, Swap},
 };
 use async_trait::async_trait;
 use oura::model::{TransactionRecord, TxOutputRecord};
-use sea_orm::DatabaseConnection;
+use pallas::ledger::addresses::Address;
+
+/// Fee schedule of the DEX. The batcher takes `ada_swap_in` together with the swap request and
+/// returns `ada_swap_out` together with the swapped tokens, the difference is the batcher fee.
+pub struct Fees {
+    // Pool fee in basis points of the input amount
+    pub swap_fee: u64,
+    pub ada_swap_in: u64,
+    pub ada_swap_out: u64,
+}
+
+/// Parameters of an order decoded from its datum.
+#[derive(Debug, Default)]
+pub struct OrderDatum {
+    // Bech32 address receiving the swapped tokens
+    pub owner: Option<String>,
+    // Minimum amount of the bought token, swap orders only
+    pub min_receive: Option<u64>,
+    // Unix timestamp after which the order can't be executed
+    pub deadline: Option<i64>,
+    // The bought token, swap orders only
+    pub buy: Option<Asset>,
+}
 
 #[async_trait]
 pub trait Dex {
+    fn fees(&self) -> Fees;
+    fn order(&self, datum: &serde_json::Value) -> OrderDatum;
     async fn mean_value(
         &self,
         pool: &PoolConfig,
-        db: &DatabaseConnection,
         transaction: &TransactionRecord,
     ) -> Option<(AssetAmount, AssetAmount)>;
     async fn swaps(
         &self,
         pool: &PoolConfig,
-        db: &DatabaseConnection,
+        utxos: &UtxoCache,
         transaction: &TransactionRecord,
     ) -> anyhow::Result<Vec<Swap>>;
 }
 
+/// Datum of the output attached to the transaction, if any.
+pub fn get_datum<'a>(
+    transaction: &'a TransactionRecord,
+    output: &TxOutputRecord,
+) -> Option<&'a serde_json::Value> {
+    let datum_hash = output.datum_hash.as_ref()?;
+    transaction
+        .plutus_data
+        .iter()
+        .flatten()
+        .find(|p| p.datum_hash == *datum_hash)
+        .map(|p| &p.plutus_data)
+}
+
+/// Token of the Plutus `AssetClass` data, Constr 0 [policy id, name].
+pub fn get_plutus_asset(asset: &serde_json::Value) -> Option<Asset> {
+    Some(Asset {
+        policy_id: asset["fields"][0]["bytes"].as_str()?.to_string(),
+        name: asset["fields"][1]["bytes"].as_str()?.to_string(),
+    })
+}
+
+/// Mainnet bech32 address of the Plutus `Address` data. Addresses with a stake pointer are not
+/// supported.
+pub fn get_plutus_address(address: &serde_json::Value) -> Option<String> {
+    // Credentials are Constr 0 [key hash] or Constr 1 [script hash]
+    let payment = &address["fields"][0];
+    let payment_script = payment["constructor"].as_i64()? as u8;
+    let mut bytes = hex::decode(payment["fields"][0]["bytes"].as_str()?).ok()?;
+    // Maybe (StakingHash credential)
+    let stake = &address["fields"][1];
+    let header = match stake["constructor"].as_i64()? {
+        0 => {
+            let credential = &stake["fields"][0]["fields"][0];
+            if stake["fields"][0]["constructor"].as_i64()? != 0 {
+                return None;
+            }
+            let stake_script = credential["constructor"].as_i64()? as u8;
+            bytes.extend(hex::decode(credential["fields"][0]["bytes"].as_str()?).ok()?);
+            (stake_script << 5) | (payment_script << 4) | 0x01
+        }
+        _ => 0x60 | (payment_script << 4) | 0x01,
+    };
+    bytes.insert(0, header);
+    Address::from_bytes(&bytes).ok()?.to_bech32().ok()
+}
+
 pub fn get_amount(output: &TxOutputRecord, policy_id: &str, asset: &str) -> u64 {
     if asset.is_empty() && policy_id.is_empty() {
-        return output.amount as u64;
+        return output.amount;
     }
     output
         .assets
         .iter()
         .flatten()
         .filter(|a| a.asset == *asset && a.policy == *policy_id)
-        .fold(0, |sum, a| sum + a.amount) as u64
+        .fold(0, |sum, a| sum + a.amount)
+}
+
+/// Reference of the output spent by the transaction input at the index.
+pub fn get_input_reference(
+    transaction: &TransactionRecord,
+    index: usize,
+) -> Option<OutputReference> {
+    let input = transaction.inputs.as_ref()?.get(index)?;
+    Some(OutputReference {
+        tx_hash: input.tx_id.clone(),
+        index: input.index,
+    })
 }
 
 pub fn reduce_ada_amount(policy_id: &str, asset: &str, amount: u64) -> u64 {
@@ -1,14 +1,12 @@
 use crate::{
+    cache::UtxoCache,
     config::{MinSwapV1, PoolConfig},
-    queries,
     sink::common,
     types::{Asset, AssetAmount, Swap},
     utils,
 };
 use async_trait::async_trait;
 use oura::model::{TransactionRecord, TxOutputRecord};
-use pallas::ledger::addresses::Address;
-use sea_orm::DatabaseConnection;
 
 static MS1_ADA_SWAP_IN: u64 = 4_000_000;
 static MS1_ADA_SWAP_OUT: u64 = 2_000_000;
@@ -38,31 +36,38 @@ fn extract_plutus(datum: &serde_json::Value) -> (Asset, Asset) {
     )
 }
 
-#[allow(dead_code)]
-pub fn get_address_from_plutus(datum: &serde_json::Value) -> String {
-    let first = datum["fields"][1]["fields"][0]["fields"][0]["bytes"]
-        .as_str()
-        .unwrap()
-        .to_string();
-
-    let second = datum["fields"][1]["fields"][1]["fields"][0]["fields"][0]["fields"][0]["bytes"]
-        .as_str()
-        .unwrap()
-        .to_string();
-
-    let string_list = vec![String::from("01"), first, second];
-    Address::from_hex(&string_list.join(""))
-        .unwrap()
-        .to_bech32()
-        .unwrap()
+pub fn get_address_from_plutus(datum: &serde_json::Value) -> Option<String> {
+    // The order datum is Constr 0 [sender, receiver, receiver datum hash, step, ...]
+    common::get_plutus_address(&datum["fields"][1])
 }
 
 #[async_trait]
 impl common::Dex for MinSwapV1 {
+    fn fees(&self) -> common::Fees {
+        common::Fees {
+            swap_fee: 30,
+            ada_swap_in: MS1_ADA_SWAP_IN,
+            ada_swap_out: MS1_ADA_SWAP_OUT,
+        }
+    }
+    fn order(&self, datum: &serde_json::Value) -> common::OrderDatum {
+        common::OrderDatum {
+            owner: get_address_from_plutus(datum),
+            // SwapExactIn [desired coin, minimum receive]
+            min_receive: match datum["fields"][3]["constructor"].as_i64() {
+                Some(0) => datum["fields"][3]["fields"][1]["int"].as_u64(),
+                _ => None,
+            },
+            deadline: None,
+            buy: match datum["fields"][3]["constructor"].as_i64() {
+                Some(0) => common::get_plutus_asset(&datum["fields"][3]["fields"][0]),
+                _ => None,
+            },
+        }
+    }
     async fn mean_value(
         &self,
         pool: &PoolConfig,
-        _db: &DatabaseConnection,
         transaction: &TransactionRecord,
     ) -> Option<(AssetAmount, AssetAmount)> {
         let script_hash = hex::decode(&pool.script_hash).unwrap();
@@ -110,11 +115,11 @@ impl common::Dex for MinSwapV1 {
     async fn swaps(
         &self,
         pool: &PoolConfig,
-        db: &DatabaseConnection,
+        utxos: &UtxoCache,
         transaction: &TransactionRecord,
     ) -> anyhow::Result<Vec<Swap>> {
         let script_hash = hex::decode(&pool.script_hash).unwrap();
-        let inputs = queries::get_utxo_input(transaction.inputs.as_ref().unwrap(), db).await;
+        let inputs = utxos.resolve(transaction.inputs.as_ref().unwrap()).await;
         // https://cardanoscan.io/transaction/28956fc5b99977c520ce31eb49ad8fafd76fba9a9035ca5b2066a9d1741deb4d?tab=utxo
         let mut swaps: Vec<Swap> = Vec::new();
 
@@ -138,11 +143,12 @@ impl common::Dex for MinSwapV1 {
             );
 
             // Get all input coresponding with correct address and plutus datum
-            for input in inputs
+            for (index, input) in inputs
                 .iter()
                 .flatten()
-                .flatten()
-                .filter(|i| i.address == pool.address && i.datum_hash.is_some())
+                .enumerate()
+                .filter_map(|(index, input)| Some((index, input.as_ref()?)))
+                .filter(|(_, i)| i.address == pool.address && i.datum_hash.is_some())
             {
                 if let Some(datum) = transaction
                     .plutus_data
@@ -172,73 +178,89 @@ impl common::Dex for MinSwapV1 {
                             .to_string();
 
                         // Get transaction output - second way how to get output
-                        let address = get_address_from_plutus(&datum.plutus_data);
+                        let order = self.order(&datum.plutus_data);
+                        let address = match order.owner {
+                            Some(address) => address,
+                            None => continue,
+                        };
                         // Get coresponding UTxO with result
-                        let utxo_pos = free_utxo.iter().position(|o| o.address == address).unwrap();
-                        let utxo = free_utxo[utxo_pos];
-                        // Remove this UTxO as used
-                        free_utxo.remove(utxo_pos);
-                        // Get amount and direction
-                        // Very ugly match case....
-                        let (amount1, amount2, direction) = match main_asset2.name == asset
-                            && main_asset2.policy_id == policy_id
+                        if let Some(utxo_pos) = free_utxo.iter().position(|o| o.address == address)
                         {
-                            true => (
-                                common::get_amount(
-                                    input,
-                                    &main_asset1.policy_id,
-                                    &main_asset1.name,
-                                ) - common::reduce_ada_amount(
-                                    &main_asset1.policy_id,
-                                    &main_asset1.name,
-                                    MS1_ADA_SWAP_IN,
-                                ),
-                                common::get_amount(utxo, &main_asset2.policy_id, &main_asset2.name)
-                                    - common::reduce_ada_amount(
+                            let utxo = free_utxo[utxo_pos];
+                            // Remove this UTxO as used
+                            free_utxo.remove(utxo_pos);
+                            // Get amount and direction
+                            // Very ugly match case....
+                            let (amount1, amount2, direction) = match main_asset2.name == asset
+                                && main_asset2.policy_id == policy_id
+                            {
+                                true => (
+                                    common::get_amount(
+                                        input,
+                                        &main_asset1.policy_id,
+                                        &main_asset1.name,
+                                    ) - common::reduce_ada_amount(
+                                        &main_asset1.policy_id,
+                                        &main_asset1.name,
+                                        MS1_ADA_SWAP_IN,
+                                    ),
+                                    common::get_amount(
+                                        utxo,
+                                        &main_asset2.policy_id,
+                                        &main_asset2.name,
+                                    ) - common::reduce_ada_amount(
                                         &main_asset2.policy_id,
                                         &main_asset2.name,
                                         MS1_ADA_SWAP_OUT,
                                     ),
-                                false,
-                            ),
-                            false => (
-                                common::get_amount(utxo, &main_asset1.policy_id, &main_asset1.name)
-                                    - common::reduce_ada_amount(
+                                    false,
+                                ),
+                                false => (
+                                    common::get_amount(
+                                        utxo,
+                                        &main_asset1.policy_id,
+                                        &main_asset1.name,
+                                    ) - common::reduce_ada_amount(
                                         &main_asset1.policy_id,
                                         &main_asset1.name,
                                         MS1_ADA_SWAP_OUT,
                                     ),
-                                common::get_amount(
-                                    input,
-                                    &main_asset2.policy_id,
-                                    &main_asset2.name,
-                                ) - common::reduce_ada_amount(
-                                    &main_asset2.policy_id,
-                                    &main_asset2.name,
-                                    MS1_ADA_SWAP_IN,
+                                    common::get_amount(
+                                        input,
+                                        &main_asset2.policy_id,
+                                        &main_asset2.name,
+                                    ) - common::reduce_ada_amount(
+                                        &main_asset2.policy_id,
+                                        &main_asset2.name,
+                                        MS1_ADA_SWAP_IN,
+                                    ),
+                                    true,
                                 ),
-                                true,
-                            ),
-                        };
+                            };
 
-                        // Add swap to the result
-                        swaps.push(Swap {
-                            first: AssetAmount {
-                                asset: Asset {
-                                    policy_id: main_asset1.policy_id.clone(),
-                                    name: main_asset1.name.clone(),
+                            // Add swap to the result
+                            swaps.push(Swap {
+                                first: AssetAmount {
+                                    asset: Asset {
+                                        policy_id: main_asset1.policy_id.clone(),
+                                        name: main_asset1.name.clone(),
+                                    },
+                                    amount: amount1,
                                 },
-                                amount: amount1 as u64,
-                            },
-                            second: AssetAmount {
-                                asset: Asset {
-                                    policy_id: main_asset2.policy_id.clone(),
-                                    name: main_asset2.name.clone(),
+                                second: AssetAmount {
+                                    asset: Asset {
+                                        policy_id: main_asset2.policy_id.clone(),
+                                        name: main_asset2.name.clone(),
+                                    },
+                                    amount: amount2,
                                 },
-                                amount: amount2 as u64,
-                            },
-                            direction,
-                        })
+                                direction,
+                                owner: Some(address),
+                                order: common::get_input_reference(transaction, index),
+                                min_receive: order.min_receive,
+                                deadline: order.deadline,
+                            });
+                        }
                     }
                 }
             }
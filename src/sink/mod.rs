@@ -1,10 +1,20 @@
+use std::collections::HashSet;
+
 use crate::{
-    config, queries, server,
-    types::{BroadcastMessage, ExchangeRate, SwapInfo},
+    arbitrage, batcher,
+    cache::{TokenRegistry, UtxoCache},
+    candles, config, liquidity, metadata, mev, orderbook, queries, registry, server, stats,
+    types::{
+        BroadcastMessage, EventOrigin, ExchangeRate, OutputReference, PoolState, Swap, SwapInfo,
+    },
     utils,
 };
 
-use oura::{model::EventData, pipelining::StageReceiver};
+use oura::{
+    model::{EventData, TxInputRecord},
+    pipelining::StageReceiver,
+    utils::ChainWellKnownInfo,
+};
 use sea_orm::DatabaseConnection;
 
 pub mod common;
@@ -14,13 +24,28 @@ pub mod wingriders_v1;
 
 pub async fn start(
     input: StageReceiver,
-    db: DatabaseConnection,
-    pools: &[config::PoolConfig],
+    db: Option<DatabaseConnection>,
     persistent: bool,
+    pools: &[config::PoolConfig],
+    aggregation: &config::AggregationConfig,
+    well_known: &ChainWellKnownInfo,
+    utxo_cache_size: usize,
 ) -> anyhow::Result<()> {
     tracing::info!("Starting");
     let pools: Vec<&config::PoolConfig> = pools.iter().filter(|p| p.enable).collect();
 
+    // Without a database wtp works as a stateless price feed, without the persistent mode the
+    // database is only read
+    let store = db.as_ref().filter(|_| persistent);
+    let mut utxos = UtxoCache::new(&pools, utxo_cache_size, db.clone());
+    utxos.load().await?;
+    let mut tokens = TokenRegistry::new(db.clone(), persistent);
+    if let Some(db) = &db {
+        liquidity::load(db, aggregation).await?;
+        orderbook::load(&pools, &mut tokens, db).await?;
+        stats::load(db).await?;
+    }
+
     loop {
         let event = input.recv()?;
 
@@ -30,26 +55,71 @@ pub async fn start(
                 block_hash,
             } => {
                 tracing::debug!("Rollback, current block: {} {}", block_slot, block_hash);
-                if persistent {
-                    queries::rollback_to_slot(block_slot, &db).await?;
+                utxos.rollback_to_slot(*block_slot);
+                match store {
+                    Some(db) => {
+                        let affected =
+                            queries::get_price_updates_after_slot(block_slot, db).await?;
+                        queries::rollback_to_slot(block_slot, db).await?;
+                        candles::recompute(&affected, db).await?;
+                        liquidity::load(db, aggregation).await?;
+                        orderbook::load(&pools, &mut tokens, db).await?;
+                        stats::load(db).await?;
+                    }
+                    // Nothing was written, so the in-memory state is reverted instead
+                    None => {
+                        liquidity::rollback_to_slot(*block_slot);
+                        orderbook::rollback_to_slot(*block_slot);
+                        stats::rollback_to_slot(*block_slot);
+                    }
                 }
             }
 
             EventData::Block(block) => {
                 tracing::debug!("Block: {} {}", block.slot, block.hash);
 
-                let block_id = match persistent {
-                    true => Some(queries::insert_block(block, &db).await?),
-                    _ => None,
+                let timestamp = utils::slot_to_timestamp(well_known, block.slot);
+                let block_id = match store {
+                    Some(db) => Some(queries::insert_block(block, timestamp, db).await?),
+                    None => None,
                 };
 
-                for transaction_record in block.transactions.iter().flatten() {
+                let invalid_transactions = utils::get_invalid_transactions(block)?;
+                utxos.start_block(block.slot);
+                liquidity::start_block(block.slot);
+                orderbook::start_block(block.slot, timestamp);
+                stats::start_block(block.slot, timestamp);
+                // Swaps of the block in the order of the execution, checked for MEV at the end
+                let mut block_swaps = Vec::new();
+                // Pairs with new reserves, compared across their pools at the end
+                let mut changed_pairs = Vec::new();
+                // Stored open orders spent in the block, also those no longer in the UTxO cache,
+                // so that cancellations creating no watched output are recorded too
+                let spent_orders = match store {
+                    Some(db) => {
+                        let inputs: Vec<TxInputRecord> = block
+                            .transactions
+                            .iter()
+                            .flatten()
+                            .enumerate()
+                            .filter(|(index, _)| !invalid_transactions.contains(index))
+                            .flat_map(|(_, t)| t.inputs.iter().flatten().cloned())
+                            .collect();
+                        queries::get_spent_orders(&inputs, db).await?
+                    }
+                    None => HashSet::new(),
+                };
+
+                for (index, transaction_record) in block.transactions.iter().flatten().enumerate() {
+                    let valid = !invalid_transactions.contains(&index);
+                    let outputs = utils::get_produced_outputs(transaction_record, valid);
+
                     let watched = pools.iter().any(|p| {
                         let pool_hash = hex::decode(&p.script_hash).unwrap();
                         let request_hash = hex::decode(&p.request_hash).unwrap();
                         let vesting_hash = hex::decode(&p.vesting_hash).unwrap();
 
-                        transaction_record.outputs.iter().flatten().any(|o| {
+                        outputs.iter().any(|(_, o)| {
                             let hash = utils::get_payment_hash(&o.address).unwrap_or_default();
 
                             pool_hash == hash
@@ -57,48 +127,183 @@ pub async fn start(
                                 || vesting_hash == hash
                                 || o.address == p.address
                         })
-                    });
-
-                    let tx_id = match (persistent, watched) {
-                        (true, true) => Some(
-                            queries::insert_transaction(transaction_record, block_id.unwrap(), &db)
-                                .await?,
-                        ),
-                        _ => None,
+                    }) || utxos.spends_watched(transaction_record)
+                        || (valid
+                            && transaction_record
+                                .inputs
+                                .iter()
+                                .flatten()
+                                .any(|i| spent_orders.contains(&(i.tx_id.clone(), i.index))));
+
+                    let (tx_id, replaced) = match (store, watched) {
+                        (Some(db), true) => {
+                            let (tx_id, replaced) = queries::insert_transaction(
+                                transaction_record,
+                                block_id.unwrap(),
+                                valid,
+                                db,
+                            )
+                            .await?;
+                            (Some(tx_id), replaced)
+                        }
+                        _ => (None, false),
                     };
 
+                    // Failed transactions only consume collateral, so no DEX operation happened
+                    if !valid {
+                        tracing::info!("Skipping invalid transaction {}", transaction_record.hash);
+                        utxos.apply_transaction(transaction_record, valid);
+                        continue;
+                    }
+
+                    for (asset, token_metadata) in metadata::get_token_metadata(transaction_record)
+                    {
+                        tokens.update_metadata(&asset, token_metadata).await?;
+                    }
+
+                    // Orders placed by the transaction, each DEX pool has its own request address
+                    let mut changed_pools = Vec::new();
+                    for (index, output) in outputs.iter() {
+                        let hash = utils::get_payment_hash(&output.address);
+                        let pool = match pools
+                            .iter()
+                            .find(|p| hash == hex::decode(&p.request_hash).ok())
+                        {
+                            Some(pool) => pool,
+                            None => continue,
+                        };
+                        let datum = common::get_datum(transaction_record, output);
+                        let order = datum.map(|d| pool.as_trait().order(d)).unwrap_or_default();
+                        if let (Some(db), Some(tx_id)) = (store, tx_id) {
+                            queries::insert_order(
+                                tx_id,
+                                &hex::decode(&pool.script_hash).unwrap(),
+                                *index,
+                                &order,
+                                datum,
+                                db,
+                            )
+                            .await?;
+                        }
+                        let output_ref = (transaction_record.hash.clone(), *index as u64);
+                        if let Some(key) =
+                            orderbook::place(pool, output_ref, output, &order, &mut tokens).await
+                        {
+                            changed_pools.push(key);
+                        }
+                    }
+
+                    // Orders executed by the transaction, the others it spends are refunded in
+                    // a batch or cancelled otherwise
+                    let mut executed: Vec<OutputReference> = Vec::new();
+                    let mut batch = false;
+
                     for pool in pools.iter() {
                         let script_hash = hex::decode(&pool.script_hash).unwrap();
                         let dex_trait = pool.as_trait();
                         if let Some((asset1, asset2)) =
-                            dex_trait.mean_value(pool, &db, transaction_record).await
+                            dex_trait.mean_value(pool, transaction_record).await
                         {
-                            let asset1_id = queries::get_token_id(&asset1.asset, &db).await?;
-                            let asset2_id = queries::get_token_id(&asset2.asset, &db).await?;
+                            // Pairs are stored in the canonical order, whatever order the DEX uses
+                            let reversed = !utils::is_canonical_pair(&asset1.asset, &asset2.asset);
+                            let (asset1, asset2) = match reversed {
+                                true => (asset2, asset1),
+                                false => (asset1, asset2),
+                            };
+                            let asset1_id = tokens.get_id(&asset1.asset).await?;
+                            let asset2_id = tokens.get_id(&asset2.asset).await?;
 
+                            // The pool output carrying the new reserves
+                            let pool_output = transaction_record
+                                .outputs
+                                .iter()
+                                .flatten()
+                                .position(|o| {
+                                    o.address == pool.address
+                                        || utils::get_payment_hash(&o.address).as_ref()
+                                            == Some(&script_hash)
+                                })
+                                .map(|index| OutputReference {
+                                    tx_hash: transaction_record.hash.clone(),
+                                    index: index as u64,
+                                });
+                            let origin = EventOrigin {
+                                event_id: utils::get_price_event_id(
+                                    &transaction_record.hash,
+                                    &pool.script_hash,
+                                ),
+                                tx_hash: transaction_record.hash.clone(),
+                                block_hash: block.hash.clone(),
+                                slot: block.slot,
+                                output: pool_output.clone(),
+                            };
+
+                            let rate = utils::get_rate(asset1.amount, asset2.amount);
                             let exchange_rate = ExchangeRate {
                                 asset1: asset1_id,
                                 asset2: asset2_id,
                                 script_hash: pool.script_hash.clone(),
-                                rate: asset1.amount as f64 / asset2.amount as f64,
+                                rate,
+                                adjusted_rate: utils::get_adjusted_rate(
+                                    rate,
+                                    registry::get(&asset1.asset).and_then(|m| m.decimals),
+                                    registry::get(&asset2.asset).and_then(|m| m.decimals),
+                                ),
+                                timestamp: Some(timestamp),
+                                origin,
                             };
                             server::ws_broadcast(&BroadcastMessage::MeanValue(exchange_rate));
 
-                            if let Some(tx_id) = tx_id {
+                            // The swaps of the transaction were executed against these reserves
+                            let previous_state =
+                                liquidity::get_pool_state(&pool.script_hash, asset1_id, asset2_id);
+                            let pool_state = PoolState {
+                                script_hash: pool.script_hash.clone(),
+                                asset1: asset1_id,
+                                asset2: asset2_id,
+                                reserve1: asset1.clone(),
+                                reserve2: asset2.clone(),
+                                timestamp,
+                            };
+                            changed_pairs.push((asset1_id, asset2_id));
+                            if let Some(price) = liquidity::update(pool_state, aggregation) {
+                                server::ws_broadcast(&BroadcastMessage::AggregatedPrice(price));
+                            }
+
+                            if let (Some(db), Some(tx_id)) = (store, tx_id) {
                                 queries::insert_price_update(
                                     tx_id,
                                     &script_hash,
                                     asset1_id,
-                                    asset1.amount as i64,
                                     asset2_id,
-                                    asset2.amount as i64,
-                                    &db,
+                                    (asset1.amount as i64, asset2.amount as i64),
+                                    pool_output.map(|o| o.index as i64),
+                                    db,
                                 )
                                 .await?;
                             }
-                            let swaps = dex_trait.swaps(pool, &db, transaction_record).await?;
-                            for swap in swaps.iter() {
+                            let swaps: Vec<Swap> = dex_trait
+                                .swaps(pool, &utxos, transaction_record)
+                                .await?
+                                .into_iter()
+                                .map(|s| if reversed { s.reversed() } else { s })
+                                .collect();
+                            for (position, swap) in swaps.iter().enumerate() {
+                                let (payment_credential, stake_credential) = swap
+                                    .owner
+                                    .as_deref()
+                                    .and_then(utils::get_credentials)
+                                    .unwrap_or_default();
+                                // The adapters find the swaps in a deterministic order, so the
+                                // position identifies a swap with an unknown order as well
+                                let event_id = utils::get_swap_event_id(
+                                    &transaction_record.hash,
+                                    &pool.script_hash,
+                                    swap.order.as_ref(),
+                                    position as u64,
+                                );
                                 let swap_info = SwapInfo {
+                                    script_hash: pool.script_hash.clone(),
                                     asset1: asset1_id,
                                     amount1: swap.first.amount as i64,
                                     asset2: asset2_id,
@@ -107,16 +312,113 @@ pub async fn start(
                                         true => "Buy".to_string(),
                                         false => "Sell".to_string(),
                                     },
+                                    timestamp: Some(timestamp),
+                                    min_receive: swap.min_receive,
+                                    deadline: swap.deadline,
+                                    slippage: previous_state.as_ref().and_then(|state| {
+                                        swap.slippage(state.reserve1.amount, state.reserve2.amount)
+                                    }),
+                                    payment_credential: payment_credential.map(hex::encode),
+                                    stake_credential: stake_credential.map(hex::encode),
+                                    origin: EventOrigin {
+                                        event_id,
+                                        tx_hash: transaction_record.hash.clone(),
+                                        block_hash: block.hash.clone(),
+                                        slot: block.slot,
+                                        output: swap.order.clone(),
+                                    },
                                 };
-                                if let Some(tx_id) = tx_id {
-                                    queries::insert_swap(tx_id, &script_hash, &swap_info, &db)
-                                        .await?;
+                                if let (Some(db), Some(tx_id)) = (store, tx_id) {
+                                    queries::insert_swap(
+                                        tx_id,
+                                        &script_hash,
+                                        position as i64,
+                                        &swap_info,
+                                        db,
+                                    )
+                                    .await?;
                                 }
+                                block_swaps.push(mev::BlockSwap {
+                                    swap: swap_info.clone(),
+                                    tx_id,
+                                    batcher: batcher::get_batcher(transaction_record)
+                                        .map(hex::encode),
+                                });
                                 server::ws_broadcast(&BroadcastMessage::Swap(swap_info));
                             }
                             tracing::info!("SWAPS[{}] {:?}", transaction_record.hash, swaps);
+                            if let (Some(db), Some(tx_id), false) = (store, tx_id, swaps.is_empty())
+                            {
+                                batcher::record(
+                                    tx_id,
+                                    pool,
+                                    &utxos,
+                                    transaction_record,
+                                    &swaps,
+                                    timestamp,
+                                    db,
+                                )
+                                .await?;
+                            }
+                            batch = true;
+                            executed.extend(swaps.iter().filter_map(|s| s.order.clone()));
+
+                            let series = (script_hash.clone(), asset1_id, asset2_id);
+                            let volume = swaps.iter().fold((0, 0), |(v1, v2), s| {
+                                (v1 + s.first.amount as i64, v2 + s.second.amount as i64)
+                            });
+                            let trades = swaps.len() as i64;
+                            // The pool statistics count the same swaps as the hourly candles,
+                            // replaced ones were already counted
+                            if rate.is_some() && trades > 0 && !replaced {
+                                stats::add(&series, timestamp, volume, trades);
+                            }
+
+                            // Candles are not updated when the price is undefined
+                            if let (Some(db), Some(_), Some(rate)) = (store, tx_id, rate) {
+                                // Replaced rows were already counted, so rebuild those candles
+                                if replaced {
+                                    candles::recompute(&[(series, timestamp)], db).await?;
+                                } else {
+                                    candles::update(&series, timestamp, rate, volume, trades, db)
+                                        .await?;
+                                }
+                            }
                         }
                     }
+
+                    if let (Some(db), Some(tx_id)) = (store, tx_id) {
+                        queries::close_orders(transaction_record, tx_id, &executed, batch, db)
+                            .await?;
+                    }
+                    changed_pools.extend(orderbook::spend(transaction_record));
+                    changed_pools.sort();
+                    changed_pools.dedup();
+                    for key in changed_pools.iter() {
+                        server::ws_broadcast(&BroadcastMessage::OrderBook(orderbook::get_depth(
+                            key, None,
+                        )));
+                    }
+
+                    utxos.apply_transaction(transaction_record, valid);
+                }
+
+                for (tx_id, alert) in mev::detect(&block_swaps, db.as_ref()).await? {
+                    tracing::info!("MEV[{}] {:?}", alert.tx_hash, alert);
+                    if let (Some(db), Some(tx_id)) = (store, tx_id) {
+                        queries::insert_alert(tx_id, &alert, db).await?;
+                    }
+                    server::ws_broadcast(&BroadcastMessage::MevAlert(alert));
+                }
+
+                changed_pairs.sort();
+                changed_pairs.dedup();
+                let opportunities = arbitrage::find(&changed_pairs, &pools, block, timestamp);
+                if let (Some(db), Some(block_id)) = (store, block_id) {
+                    queries::insert_arbitrage_opportunities(block_id, &opportunities, db).await?;
+                }
+                for opportunity in opportunities {
+                    server::ws_broadcast(&BroadcastMessage::Arbitrage(opportunity));
                 }
             }
             _ => {
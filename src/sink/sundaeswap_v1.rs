@@ -1,14 +1,12 @@
 use crate::{
+    cache::UtxoCache,
     config::{PoolConfig, SundaeSwapV1},
-    queries,
     sink::common,
     types::{Asset, AssetAmount, Swap},
     utils,
 };
 use async_trait::async_trait;
 use oura::model::{TransactionRecord, TxOutputRecord};
-use pallas::ledger::addresses::Address;
-use sea_orm::DatabaseConnection;
 
 static SS1_ADA_SWAP_IN: u64 = 4_500_000;
 static SS1_ADA_SWAP_OUT: u64 = 2_000_000;
@@ -38,32 +36,37 @@ fn extract_plutus(datum: &serde_json::Value) -> (Asset, Asset) {
     )
 }
 
-#[allow(dead_code)]
-pub fn get_address_from_plutus(datum: &serde_json::Value) -> String {
-    let first = datum["fields"][1]["fields"][0]["fields"][0]["fields"][0]["fields"][0]["bytes"]
-        .as_str()
-        .unwrap()
-        .to_string();
-
-    let second = datum["fields"][1]["fields"][0]["fields"][0]["fields"][1]["fields"][0]["fields"]
-        [0]["fields"][0]["bytes"]
-        .as_str()
-        .unwrap()
-        .to_string();
-
-    let string_list = vec![String::from("01"), first, second];
-    Address::from_hex(&string_list.join(""))
-        .unwrap()
-        .to_bech32()
-        .unwrap()
+pub fn get_address_from_plutus(datum: &serde_json::Value) -> Option<String> {
+    // The escrow datum is Constr 0 [pool ident, addresses [destination [address, datum]], ...]
+    common::get_plutus_address(&datum["fields"][1]["fields"][0]["fields"][0])
 }
 
 #[async_trait]
 impl common::Dex for SundaeSwapV1 {
+    fn fees(&self) -> common::Fees {
+        common::Fees {
+            // The most common fee tier, pools can set their own one in the datum
+            swap_fee: 30,
+            ada_swap_in: SS1_ADA_SWAP_IN,
+            ada_swap_out: SS1_ADA_SWAP_OUT,
+        }
+    }
+    fn order(&self, datum: &serde_json::Value) -> common::OrderDatum {
+        common::OrderDatum {
+            owner: get_address_from_plutus(datum),
+            // Swap [direction, amount, Maybe minimum receive]
+            min_receive: match datum["fields"][3]["constructor"].as_i64() {
+                Some(0) => datum["fields"][3]["fields"][2]["fields"][0]["int"].as_u64(),
+                _ => None,
+            },
+            deadline: None,
+            // The order refers to the pool by its ident only
+            buy: None,
+        }
+    }
     async fn mean_value(
         &self,
         pool: &PoolConfig,
-        _db: &DatabaseConnection,
         transaction: &TransactionRecord,
     ) -> Option<(AssetAmount, AssetAmount)> {
         if let Some(output) = transaction
@@ -99,7 +102,7 @@ impl common::Dex for SundaeSwapV1 {
     async fn swaps(
         &self,
         pool: &PoolConfig,
-        db: &DatabaseConnection,
+        utxos: &UtxoCache,
         transaction: &TransactionRecord,
     ) -> anyhow::Result<Vec<Swap>> {
         let mut swaps: Vec<Swap> = Vec::new();
@@ -117,13 +120,16 @@ impl common::Dex for SundaeSwapV1 {
             {
                 let (asset1, asset2) = extract_plutus(&datum.plutus_data);
                 let order_hash = hex::decode(&pool.request_hash).unwrap();
-                let inputs =
-                    queries::get_utxo_input(transaction.inputs.as_ref().unwrap(), db).await;
+                let inputs = utxos.resolve(transaction.inputs.as_ref().unwrap()).await;
                 let mut free_utxo: Vec<&TxOutputRecord> =
                     transaction.outputs.iter().flatten().collect();
 
-                for input in
-                    inputs.iter().flatten().flatten().filter(|i| {
+                for (index, input) in inputs
+                    .iter()
+                    .flatten()
+                    .enumerate()
+                    .filter_map(|(index, input)| Some((index, input.as_ref()?)))
+                    .filter(|(_, i)| {
                         utils::get_payment_hash(&i.address) == Some(order_hash.to_vec())
                     })
                 {
@@ -139,7 +145,11 @@ impl common::Dex for SundaeSwapV1 {
                         .unwrap()
                         == 0
                     {
-                        let address = get_address_from_plutus(&plutus.plutus_data);
+                        let order = self.order(&plutus.plutus_data);
+                        let address = match order.owner {
+                            Some(address) => address,
+                            None => continue,
+                        };
                         if let Some(utxo_pos) = free_utxo.iter().position(|o| o.address == address)
                         {
                             let utxo = free_utxo[utxo_pos];
@@ -191,16 +201,20 @@ impl common::Dex for SundaeSwapV1 {
                                         policy_id: asset1.policy_id.clone(),
                                         name: asset1.name.clone(),
                                     },
-                                    amount: amount1 as u64,
+                                    amount: amount1,
                                 },
                                 second: AssetAmount {
                                     asset: Asset {
                                         policy_id: asset2.policy_id.clone(),
                                         name: asset2.name.clone(),
                                     },
-                                    amount: amount2 as u64,
+                                    amount: amount2,
                                 },
                                 direction,
+                                owner: Some(address),
+                                order: common::get_input_reference(transaction, index),
+                                min_receive: order.min_receive,
+                                deadline: order.deadline,
                             });
                         }
                     }
@@ -1,13 +1,12 @@
 use crate::{
+    cache::UtxoCache,
     config::{PoolConfig, WingRidersV1},
-    queries,
     sink::common,
     types::{Asset, AssetAmount, Swap},
     utils,
 };
 use async_trait::async_trait;
 use oura::model::TransactionRecord;
-use sea_orm::DatabaseConnection;
 
 static WR_ADA_POOL: u64 = 3_000_000;
 static WR_ADA_SWAP_IN: u64 = 4_000_000;
@@ -46,10 +45,43 @@ fn wr_extract_plutus_assets(datum: &serde_json::Value) -> (AssetAmount, AssetAmo
 
 #[async_trait]
 impl common::Dex for WingRidersV1 {
+    fn fees(&self) -> common::Fees {
+        common::Fees {
+            swap_fee: 35,
+            ada_swap_in: WR_ADA_SWAP_IN,
+            ada_swap_out: WR_ADA_SWAP_OUT,
+        }
+    }
+    fn order(&self, datum: &serde_json::Value) -> common::OrderDatum {
+        // The request datum is Constr 0 [metadata [beneficiary, owner, deadline, asset A,
+        // asset B], action]
+        let metadata = &datum["fields"][0];
+        let action = &datum["fields"][1];
+        common::OrderDatum {
+            owner: common::get_plutus_address(&metadata["fields"][0]),
+            // Swap [direction, minimum receive]
+            min_receive: match action["constructor"].as_i64() {
+                Some(0) => action["fields"][1]["int"].as_u64(),
+                _ => None,
+            },
+            // POSIX time in milliseconds
+            deadline: metadata["fields"][2]["int"]
+                .as_i64()
+                .map(|deadline| deadline / 1000),
+            // Direction Constr 0 swaps A to B, Constr 1 B to A
+            buy: match (
+                action["constructor"].as_i64(),
+                action["fields"][0]["constructor"].as_i64(),
+            ) {
+                (Some(0), Some(0)) => common::get_plutus_asset(&metadata["fields"][4]),
+                (Some(0), Some(1)) => common::get_plutus_asset(&metadata["fields"][3]),
+                _ => None,
+            },
+        }
+    }
     async fn mean_value(
         &self,
         pool: &PoolConfig,
-        _db: &DatabaseConnection,
         transaction: &TransactionRecord,
     ) -> Option<(AssetAmount, AssetAmount)> {
         let script_hash = hex::decode(&pool.script_hash).unwrap();
@@ -107,7 +139,7 @@ impl common::Dex for WingRidersV1 {
     async fn swaps(
         &self,
         _pool: &PoolConfig,
-        db: &DatabaseConnection,
+        utxos: &UtxoCache,
         transaction: &TransactionRecord,
     ) -> anyhow::Result<Vec<Swap>> {
         // Map inputs
@@ -136,14 +168,25 @@ impl common::Dex for WingRidersV1 {
                     .collect();
 
                 // Restore inputs
-                let inputs =
-                    queries::get_utxo_input(&transaction.inputs.clone().unwrap(), db).await?;
+                let inputs = utxos.resolve(&transaction.inputs.clone().unwrap()).await?;
                 // Zip outputs with redemeer index
 
+                // The pool UTxO is unknown e.g. right after the start of the stateless mode
+                let pool_datum_hash = match &inputs[pool_input] {
+                    Some(pool_utxo) => pool_utxo.datum_hash.clone().unwrap(),
+                    None => {
+                        tracing::info!("Missing pool UTxO on {}", transaction.hash);
+                        return Ok(swaps);
+                    }
+                };
+
                 // get information about swap from pool plutus data
-                if let Some(pool_datum) = transaction.plutus_data.iter().flatten().find(|p| {
-                    p.datum_hash == inputs[pool_input].clone().unwrap().datum_hash.unwrap()
-                }) {
+                if let Some(pool_datum) = transaction
+                    .plutus_data
+                    .iter()
+                    .flatten()
+                    .find(|p| p.datum_hash == pool_datum_hash)
+                {
                     let (asset1, asset2) =
                         wr_extract_plutus_assets(&pool_datum.plutus_data["fields"][1]);
 
@@ -174,6 +217,7 @@ impl common::Dex for WingRidersV1 {
                                 .as_i64()
                                 .unwrap();
                             if operation == 0 {
+                                let order = self.order(&datum.plutus_data);
                                 let direction = datum.plutus_data["fields"][1]["fields"][0]
                                     ["constructor"]
                                     .as_i64()
@@ -232,7 +276,14 @@ impl common::Dex for WingRidersV1 {
                                         },
                                         amount: amount2,
                                     },
-                                    direction: direction == 0,
+                                    // Swapping A to B sells the first token like on the
+                                    // other DEXes
+                                    direction: direction != 0,
+                                    // The swapped tokens are sent to the order beneficiary
+                                    owner: Some(out.address.clone()),
+                                    order: common::get_input_reference(transaction, redeemer),
+                                    min_receive: order.min_receive,
+                                    deadline: order.deadline,
                                 })
                             } else {
                                 tracing::info!("Operation is not swap");
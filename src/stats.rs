@@ -0,0 +1,332 @@
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    sync::RwLock,
+};
+
+use rust_decimal::Decimal;
+use sea_orm::DatabaseConnection;
+
+use crate::{
+    cache::ROLLBACK_DEPTH,
+    candles::SeriesKey,
+    config::Config,
+    liquidity, queries,
+    routing::PriceGraph,
+    types::{Asset, PoolState, PoolStats, PoolVolume, TokenStats, TokenVolume},
+};
+
+static HOUR: i64 = 3_600;
+static DAY: i64 = 86_400;
+static WEEK: i64 = 7 * 86_400;
+
+static LOVELACE_PER_ADA: i64 = 1_000_000;
+
+// Fees are given in basis points.
+static FEE_DENOMINATOR: i64 = 10_000;
+
+// Hourly volumes of the pool with their running sums over the last day and week. The hours are
+// kept a day longer than the week, so a rollback can bring them back into the window.
+#[derive(Default)]
+struct PoolVolumes {
+    hours: BTreeMap<i64, PoolVolume>,
+    day: PoolVolume,
+    week: PoolVolume,
+}
+
+impl PoolVolumes {
+    // Recompute the running sums after the window moved to another hour.
+    fn recompute(&mut self, now: i64) {
+        self.hours = self.hours.split_off(&(now - WEEK - DAY));
+        self.day = PoolVolume::default();
+        self.week = PoolVolume::default();
+        for (start, volume) in self.hours.iter() {
+            if *start >= now - DAY {
+                add_volume(&mut self.day, volume, 1);
+            }
+            if *start >= now - WEEK {
+                add_volume(&mut self.week, volume, 1);
+            }
+        }
+    }
+}
+
+// Volumes of every pool as of the latest block, the windows end at its timestamp.
+struct Volumes {
+    now: i64,
+    pools: HashMap<SeriesKey, PoolVolumes>,
+}
+
+static VOLUMES: RwLock<Option<Volumes>> = RwLock::new(None);
+
+// Volumes added in a block and the timestamp of the block before.
+struct BlockChanges {
+    slot: u64,
+    now: i64,
+    added: Vec<(SeriesKey, i64, PoolVolume)>,
+}
+
+// Changes of the last `ROLLBACK_DEPTH` blocks, so rollbacks can be reverted without a database.
+static CHANGES: RwLock<VecDeque<BlockChanges>> = RwLock::new(VecDeque::new());
+
+fn add_volume(total: &mut PoolVolume, volume: &PoolVolume, sign: i64) {
+    total.volume1 += sign * volume.volume1;
+    total.volume2 += sign * volume.volume2;
+    total.trades += sign * volume.trades;
+}
+
+fn get_hour(timestamp: i64) -> i64 {
+    timestamp - timestamp.rem_euclid(HOUR)
+}
+
+/// Replace the volumes with the hourly candles stored in the database. Used on startup and after
+/// rollbacks.
+pub async fn load(db: &DatabaseConnection) -> anyhow::Result<()> {
+    let now = queries::get_last_block_timestamp(db)
+        .await?
+        .unwrap_or_default();
+    let mut pools: HashMap<SeriesKey, PoolVolumes> = HashMap::new();
+    for (key, start, volume) in queries::get_hourly_pool_volumes(now - WEEK - DAY, db).await? {
+        pools.entry(key).or_default().hours.insert(start, volume);
+    }
+    for volumes in pools.values_mut() {
+        volumes.recompute(now);
+    }
+    *VOLUMES.write().unwrap() = Some(Volumes { now, pools });
+    CHANGES.write().unwrap().clear();
+    Ok(())
+}
+
+/// Move the windows to the timestamp of the new block.
+pub fn start_block(slot: u64, timestamp: i64) {
+    let mut volumes = VOLUMES.write().unwrap();
+    let volumes = volumes.get_or_insert_with(|| Volumes {
+        now: timestamp,
+        pools: HashMap::new(),
+    });
+    let mut changes = CHANGES.write().unwrap();
+    changes.push_back(BlockChanges {
+        slot,
+        now: volumes.now,
+        added: Vec::new(),
+    });
+    while changes.len() > ROLLBACK_DEPTH {
+        changes.pop_front();
+    }
+
+    // The hourly buckets only leave the windows when the hour changes
+    let previous = std::mem::replace(&mut volumes.now, timestamp);
+    if get_hour(previous) != get_hour(timestamp) {
+        for pool in volumes.pools.values_mut() {
+            pool.recompute(timestamp);
+        }
+    }
+}
+
+/// Revert the volumes added in all blocks after the given slot.
+pub fn rollback_to_slot(slot: u64) {
+    let mut volumes = VOLUMES.write().unwrap();
+    let volumes = match volumes.as_mut() {
+        Some(volumes) => volumes,
+        None => return,
+    };
+    let mut changes = CHANGES.write().unwrap();
+    while matches!(changes.back(), Some(block) if block.slot > slot) {
+        let block = changes.pop_back().unwrap();
+        for (key, hour, volume) in block.added.iter() {
+            if let Some(total) = volumes
+                .pools
+                .get_mut(key)
+                .and_then(|p| p.hours.get_mut(hour))
+            {
+                add_volume(total, volume, -1);
+            }
+        }
+        volumes.now = block.now;
+    }
+    for pool in volumes.pools.values_mut() {
+        pool.recompute(volumes.now);
+    }
+}
+
+/// Count the swaps of the pool executed at the given time.
+pub fn add(key: &SeriesKey, timestamp: i64, volume: (i64, i64), trades: i64) {
+    let volume = PoolVolume {
+        volume1: volume.0,
+        volume2: volume.1,
+        trades,
+        ..Default::default()
+    };
+    let hour = get_hour(timestamp);
+    let mut volumes = VOLUMES.write().unwrap();
+    let volumes = volumes.get_or_insert_with(|| Volumes {
+        now: timestamp,
+        pools: HashMap::new(),
+    });
+    let pool = volumes.pools.entry(key.clone()).or_default();
+    add_volume(pool.hours.entry(hour).or_default(), &volume, 1);
+    add_volume(&mut pool.day, &volume, 1);
+    add_volume(&mut pool.week, &volume, 1);
+    if let Some(block) = CHANGES.write().unwrap().back_mut() {
+        block.added.push((key.clone(), hour, volume));
+    }
+}
+
+/// Values token amounts in ADA and USD using the current routed prices.
+struct Valuation {
+    // Price of the token in lovelace, None when there is no route to ADA
+    prices: HashMap<i64, Option<Decimal>>,
+    ada_usd: Option<Decimal>,
+}
+
+impl Valuation {
+    fn new(states: &[PoolState], stablecoins: &[Asset]) -> Self {
+        let graph = PriceGraph::current();
+        let ada = graph.get_id(&Asset {
+            policy_id: String::new(),
+            name: String::new(),
+        });
+        let mut prices = HashMap::new();
+        for asset in states.iter().flat_map(|s| [s.asset1, s.asset2]) {
+            prices.entry(asset).or_insert_with(|| match ada {
+                Some(ada) if ada == asset => Some(Decimal::ONE),
+                Some(ada) => graph.get_price(asset, ada).map(|p| p.price),
+                None => None,
+            });
+        }
+        Valuation {
+            prices,
+            ada_usd: ada
+                .and_then(|ada| graph.get_usd_price(ada, stablecoins))
+                .and_then(|p| p.adjusted_price),
+        }
+    }
+
+    fn to_ada(&self, asset: i64, amount: Decimal) -> Option<Decimal> {
+        let price = (*self.prices.get(&asset)?)?;
+        price
+            .checked_mul(amount)?
+            .checked_div(Decimal::from(LOVELACE_PER_ADA))
+    }
+
+    fn to_usd(&self, ada: Option<Decimal>) -> Option<Decimal> {
+        ada?.checked_mul(self.ada_usd?)
+    }
+}
+
+// Sum of the known values, None when none of them is known.
+fn sum_known(values: impl Iterator<Item = Option<Decimal>>) -> Option<Decimal> {
+    values.flatten().reduce(|a, b| a + b)
+}
+
+fn volume_fees(volume_ada: Option<Decimal>, swap_fee: Option<u64>) -> Option<Decimal> {
+    volume_ada?
+        .checked_mul(Decimal::from(swap_fee?))?
+        .checked_div(Decimal::from(FEE_DENOMINATOR))
+}
+
+// Volume of the pool over the window, valued in ADA.
+fn get_volume(
+    state: &PoolState,
+    volume: Option<&PoolVolume>,
+    swap_fee: Option<u64>,
+    valuation: &Valuation,
+) -> PoolVolume {
+    let mut volume = volume.cloned().unwrap_or_default();
+    // The volume is valued by the first token, ADA for the ADA pairs
+    volume.volume_ada = valuation.to_ada(state.asset1, Decimal::from(volume.volume1));
+    volume.fees_ada = volume_fees(volume.volume_ada, swap_fee);
+    volume
+}
+
+// Pool statistics with the valuation they were computed with.
+fn compute(config: &Config) -> (Vec<PoolStats>, Valuation) {
+    let states = liquidity::get_pool_states();
+    let valuation = Valuation::new(&states, &config.stablecoins);
+    let volumes = VOLUMES.read().unwrap();
+
+    let stats = states
+        .iter()
+        .map(|state| {
+            let swap_fee = config
+                .pools
+                .iter()
+                .find(|p| p.script_hash == state.script_hash)
+                .map(|p| p.as_trait().fees().swap_fee);
+            let tvl_ada = sum_known(
+                [
+                    valuation.to_ada(state.asset1, Decimal::from(state.reserve1.amount)),
+                    valuation.to_ada(state.asset2, Decimal::from(state.reserve2.amount)),
+                ]
+                .into_iter(),
+            );
+            let key = (
+                hex::decode(&state.script_hash).unwrap_or_default(),
+                state.asset1,
+                state.asset2,
+            );
+            let pool = volumes.as_ref().and_then(|v| v.pools.get(&key));
+            PoolStats {
+                script_hash: state.script_hash.clone(),
+                asset1: state.asset1,
+                asset2: state.asset2,
+                reserve1: state.reserve1.amount,
+                reserve2: state.reserve2.amount,
+                tvl_ada,
+                tvl_usd: valuation.to_usd(tvl_ada),
+                volume_24h: get_volume(state, pool.map(|p| &p.day), swap_fee, &valuation),
+                volume_7d: get_volume(state, pool.map(|p| &p.week), swap_fee, &valuation),
+            }
+        })
+        .collect();
+    (stats, valuation)
+}
+
+/// Statistics of every pool: the current reserves valued in ADA and USD, and the volume, trade
+/// count and fee revenue over the last day and week before the latest block.
+pub fn get_pool_stats(config: &Config) -> Vec<PoolStats> {
+    compute(config).0
+}
+
+fn get_token_volume(asset: i64, pools: &[(&PoolStats, &PoolVolume)]) -> TokenVolume {
+    TokenVolume {
+        volume: pools
+            .iter()
+            .map(|(s, v)| match s.asset1 == asset {
+                true => v.volume1,
+                false => v.volume2,
+            })
+            .sum(),
+        trades: pools.iter().map(|(_, v)| v.trades).sum(),
+        volume_ada: sum_known(pools.iter().map(|(_, v)| v.volume_ada)),
+        fees_ada: sum_known(pools.iter().map(|(_, v)| v.fees_ada)),
+    }
+}
+
+/// Statistics of the token over all its pools. The TVL is the value of the token's own
+/// reserves, the volume counts the trades of the token in either direction.
+pub fn get_token_stats(asset: i64, config: &Config) -> TokenStats {
+    let (pools, valuation) = compute(config);
+    let pools: Vec<PoolStats> = pools
+        .into_iter()
+        .filter(|s| s.asset1 == asset || s.asset2 == asset)
+        .collect();
+    let reserve: u64 = pools
+        .iter()
+        .map(|s| match s.asset1 == asset {
+            true => s.reserve1,
+            false => s.reserve2,
+        })
+        .sum();
+    let tvl_ada = valuation.to_ada(asset, Decimal::from(reserve));
+    let day: Vec<(&PoolStats, &PoolVolume)> = pools.iter().map(|s| (s, &s.volume_24h)).collect();
+    let week: Vec<(&PoolStats, &PoolVolume)> = pools.iter().map(|s| (s, &s.volume_7d)).collect();
+    TokenStats {
+        asset,
+        pools: pools.iter().map(|s| s.script_hash.clone()).collect(),
+        reserve,
+        tvl_ada,
+        tvl_usd: valuation.to_usd(tvl_ada),
+        volume_24h: get_token_volume(asset, &day),
+        volume_7d: get_token_volume(asset, &week),
+    }
+}
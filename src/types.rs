@@ -1,55 +1,535 @@
-use serde::Serialize;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Asset {
     pub policy_id: String,
     pub name: String,
 }
 
+#[derive(Debug, Serialize, Clone, Default, PartialEq, Eq)]
+pub struct TokenMetadata {
+    pub decimals: Option<i32>,
+    pub ticker: Option<String>,
+    pub display_name: Option<String>,
+    pub logo: Option<String>,
+}
+
+impl TokenMetadata {
+    /// Fill the fields missing here from the other metadata.
+    pub fn merge(self, other: TokenMetadata) -> TokenMetadata {
+        TokenMetadata {
+            decimals: self.decimals.or(other.decimals),
+            ticker: self.ticker.or(other.ticker),
+            display_name: self.display_name.or(other.display_name),
+            logo: self.logo.or(other.logo),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct AssetInfo {
+    #[serde(flatten)]
+    pub asset: Asset,
+    #[serde(flatten)]
+    pub metadata: TokenMetadata,
+}
+
 #[derive(Debug, Serialize, Clone)]
 pub struct AssetAmount {
     pub asset: Asset,
     pub amount: u64,
 }
 
+/// Reference of a transaction output.
+#[derive(Debug, Serialize, Clone)]
+pub struct OutputReference {
+    pub tx_hash: String,
+    pub index: u64,
+}
+
+/// Where on chain the event happened. The event id stays the same across reconnects and
+/// reindexing, so consumers can deduplicate the events by it.
+#[derive(Debug, Serialize, Clone)]
+pub struct EventOrigin {
+    pub event_id: String,
+    pub tx_hash: String,
+    pub block_hash: String,
+    pub slot: u64,
+    // The pool output of price updates, the spent order of swaps. Null for the events indexed
+    // before the references were stored.
+    pub output: Option<OutputReference>,
+}
+
 #[derive(Debug, Serialize, Clone)]
 pub struct ExchangeRate {
     pub script_hash: String,
     pub asset1: i64,
     pub asset2: i64,
-    pub rate: f64,
+    // None when the price is undefined, i.e. the reserve of the second token is zero
+    pub rate: Option<Decimal>,
+    // Rate of the whole tokens, None when the decimals of a token are unknown
+    pub adjusted_rate: Option<Decimal>,
+    pub timestamp: Option<i64>,
+    #[serde(flatten)]
+    pub origin: EventOrigin,
 }
 
 #[derive(Debug, Serialize)]
 pub struct ExchangeHistory {
+    pub script_hash: String,
     pub amount1: i64,
     pub amount2: i64,
-    pub rate: f64,
+    // None when the price is undefined, i.e. the reserve of the second token is zero
+    pub rate: Option<Decimal>,
+    // Rate of the whole tokens, None when the decimals of a token are unknown
+    pub adjusted_rate: Option<Decimal>,
     pub tx_id: i64,
+    pub timestamp: Option<i64>,
+    #[serde(flatten)]
+    pub origin: EventOrigin,
 }
 
 #[derive(Debug, Serialize)]
 pub struct Swap {
     pub first: AssetAmount,
     pub second: AssetAmount,
-    pub direction: bool, // false - buy, tru - sell
+    pub direction: bool, // false - the first token is sold, true - the first token is bought
+    // Bech32 address of the order owner, where the swapped tokens are sent
+    pub owner: Option<String>,
+    // The spent order UTxO
+    pub order: Option<OutputReference>,
+    // Minimum amount of the bought token accepted by the order
+    pub min_receive: Option<u64>,
+    // Unix timestamp after which the order can't be executed
+    pub deadline: Option<i64>,
+}
+
+impl Swap {
+    /// The same swap seen from the reversed pair.
+    pub fn reversed(self) -> Swap {
+        Swap {
+            first: self.second,
+            second: self.first,
+            direction: !self.direction,
+            owner: self.owner,
+            order: self.order,
+            min_receive: self.min_receive,
+            deadline: self.deadline,
+        }
+    }
+
+    /// Relative shortfall of the received amount against the pool price before the execution,
+    /// pool fee and price impact included.
+    pub fn slippage(&self, reserve1: u64, reserve2: u64) -> Option<Decimal> {
+        // false - the first token was sold for the second one
+        let (amount_in, amount_out, reserve_in, reserve_out) = match self.direction {
+            false => (self.first.amount, self.second.amount, reserve1, reserve2),
+            true => (self.second.amount, self.first.amount, reserve2, reserve1),
+        };
+        let expected = Decimal::from(amount_in)
+            .checked_mul(Decimal::from(reserve_out))?
+            .checked_div(Decimal::from(reserve_in))?;
+        Some(Decimal::ONE - Decimal::from(amount_out).checked_div(expected)?)
+    }
 }
 
 #[derive(Debug, Serialize)]
 pub struct SwapHistory {
+    pub script_hash: String,
     pub amount1: i64,
     pub amount2: i64,
     pub tx_id: i64,
     pub direction: String,
+    pub timestamp: Option<i64>,
+    pub min_receive: Option<u64>,
+    pub deadline: Option<i64>,
+    pub slippage: Option<Decimal>,
+    #[serde(flatten)]
+    pub origin: EventOrigin,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Clone)]
 pub struct SwapInfo {
+    pub script_hash: String,
     pub asset1: i64,
     pub amount1: i64,
     pub asset2: i64,
     pub amount2: i64,
     pub direction: String,
+    pub timestamp: Option<i64>,
+    pub min_receive: Option<u64>,
+    pub deadline: Option<i64>,
+    // Realized slippage against the pool price before the execution
+    pub slippage: Option<Decimal>,
+    // Hex encoded credentials of the trader, null when the owner is unknown
+    pub payment_credential: Option<String>,
+    pub stake_credential: Option<String>,
+    #[serde(flatten)]
+    pub origin: EventOrigin,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TraderSwap {
+    pub script_hash: String,
+    pub asset1: i64,
+    pub amount1: i64,
+    pub asset2: i64,
+    pub amount2: i64,
+    pub direction: String,
+    pub timestamp: Option<i64>,
+    pub min_receive: Option<u64>,
+    pub deadline: Option<i64>,
+    pub slippage: Option<Decimal>,
+    pub payment_credential: Option<String>,
+    #[serde(flatten)]
+    pub origin: EventOrigin,
+}
+
+/// Swaps of a wallet, all its addresses sharing the stake credential together.
+#[derive(Debug, Serialize)]
+pub struct AddressSwaps {
+    pub payment_credential: Option<String>,
+    pub stake_credential: Option<String>,
+    pub swaps: Vec<TraderSwap>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderStatus {
+    Open,
+    // Still unspent, but past its deadline
+    Expired,
+    Executed,
+    // Returned to the owner by the batcher without a swap
+    Refunded,
+    // Spent by the owner
+    Cancelled,
+}
+
+impl OrderStatus {
+    pub fn name(&self) -> &'static str {
+        match self {
+            OrderStatus::Open => "open",
+            OrderStatus::Expired => "expired",
+            OrderStatus::Executed => "executed",
+            OrderStatus::Refunded => "refunded",
+            OrderStatus::Cancelled => "cancelled",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<OrderStatus> {
+        match name {
+            "open" => Some(OrderStatus::Open),
+            "expired" => Some(OrderStatus::Expired),
+            "executed" => Some(OrderStatus::Executed),
+            "refunded" => Some(OrderStatus::Refunded),
+            "cancelled" => Some(OrderStatus::Cancelled),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct OrderInfo {
+    pub script_hash: String,
+    pub output: OutputReference,
+    pub payment_credential: Option<String>,
+    pub stake_credential: Option<String>,
+    pub min_receive: Option<u64>,
+    pub deadline: Option<i64>,
+    // The order datum as JSON, null when it wasn't attached to the placing transaction
+    pub datum: Option<serde_json::Value>,
+    pub status: String,
+    pub created_at: Option<i64>,
+    // The transaction which spent the order
+    pub closed_tx_hash: Option<String>,
+    pub closed_at: Option<i64>,
+    // Seconds from the placement to the execution, executed orders only
+    pub time_to_fill: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DepthLevel {
+    // Limit price of the second token in units of the first one, null for market orders
+    pub price: Option<Decimal>,
+    // Offered amount, of the first token for bids and of the second token for asks
+    pub amount: u64,
+    pub orders: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OrderBookDepth {
+    pub script_hash: String,
+    pub asset1: i64,
+    pub asset2: i64,
+    // Orders buying the second token, the highest price first
+    pub bids: Vec<DepthLevel>,
+    // Orders selling the second token, the lowest price first
+    pub asks: Vec<DepthLevel>,
+    pub timestamp: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Candle {
+    pub start: i64,
+    pub open: Decimal,
+    pub high: Decimal,
+    pub low: Decimal,
+    pub close: Decimal,
+    pub volume1: i64,
+    pub volume2: i64,
+    pub trades: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MeanExchangeRatio {
+    pub date: i64,
+    pub ratio: Decimal,
+    pub adjusted_ratio: Option<Decimal>,
+}
+
+/// Reserves of a pool after its latest transaction.
+#[derive(Debug, Serialize, Clone)]
+pub struct PoolState {
+    pub script_hash: String,
+    pub asset1: i64,
+    pub asset2: i64,
+    pub reserve1: AssetAmount,
+    pub reserve2: AssetAmount,
+    pub timestamp: i64,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct AggregatedPrice {
+    pub asset1: i64,
+    pub asset2: i64,
+    // Reserve weighted price over the pools which passed the outlier rules
+    pub rate: Option<Decimal>,
+    pub adjusted_rate: Option<Decimal>,
+    pub pools: Vec<String>,
+    pub reserve1: u64,
+    pub reserve2: u64,
+    pub timestamp: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RouteHop {
+    pub from: i64,
+    pub to: i64,
+    pub rate: Option<Decimal>,
+    pub pools: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RoutedPrice {
+    pub asset: i64,
+    pub quote: i64,
+    // Price of the asset in units of the quote token
+    pub price: Decimal,
+    pub adjusted_price: Option<Decimal>,
+    // The smallest reserve along the route in units of the asset
+    pub liquidity: Decimal,
+    pub route: Vec<RouteHop>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PoolQuote {
+    pub script_hash: String,
+    pub amount_out: u64,
+    // Pool fee in units of the input token
+    pub fee: u64,
+    // Lovelace kept by the batcher
+    pub batcher_fee: u64,
+    // Lovelace returned together with the swapped tokens
+    pub deposit: u64,
+    pub price_impact: Option<Decimal>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Quote {
+    pub from: i64,
+    pub to: i64,
+    pub amount: u64,
+    pub pools: Vec<PoolQuote>,
+    pub best: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct PoolVolume {
+    pub volume1: i64,
+    pub volume2: i64,
+    pub trades: i64,
+    // Volume and pool fee revenue valued in ADA, null without a route to ADA
+    pub volume_ada: Option<Decimal>,
+    pub fees_ada: Option<Decimal>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PoolStats {
+    pub script_hash: String,
+    pub asset1: i64,
+    pub asset2: i64,
+    pub reserve1: u64,
+    pub reserve2: u64,
+    pub tvl_ada: Option<Decimal>,
+    pub tvl_usd: Option<Decimal>,
+    pub volume_24h: PoolVolume,
+    pub volume_7d: PoolVolume,
+}
+
+#[derive(Debug, Serialize, Default)]
+pub struct TokenVolume {
+    pub volume: i64,
+    pub trades: i64,
+    pub volume_ada: Option<Decimal>,
+    pub fees_ada: Option<Decimal>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TokenStats {
+    pub asset: i64,
+    pub pools: Vec<String>,
+    pub reserve: u64,
+    pub tvl_ada: Option<Decimal>,
+    pub tvl_usd: Option<Decimal>,
+    pub volume_24h: TokenVolume,
+    pub volume_7d: TokenVolume,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BatchInfo {
+    pub script_hash: String,
+    pub tx_hash: String,
+    // Hex encoded key hash of the batcher, null when the transaction has no key witness
+    pub batcher: Option<String>,
+    // Orders of every kind spent by the batch
+    pub orders: u64,
+    // Lovelace kept by the batcher
+    pub batcher_fee: u64,
+    // Lovelace returned to the traders together with the swapped tokens
+    pub deposit: u64,
+    pub tx_fee: u64,
+    // Executed orders with a known placement
+    pub placed_orders: u64,
+    // Pairs of the placed orders executed in the reverse order of their placement
+    pub inversions: u64,
+    pub timestamp: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BatcherStats {
+    pub script_hash: String,
+    pub batcher: Option<String>,
+    pub batches: i64,
+    pub orders: i64,
+    pub batcher_fee: i64,
+    pub deposit: i64,
+    pub tx_fee: i64,
+    pub inversions: i64,
+    // Share of the pairs of placed orders executed in the order of their placement, null
+    // without any such pair
+    pub fairness: Option<Decimal>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ArbitrageOpportunity {
+    pub event_id: String,
+    pub asset1: i64,
+    pub asset2: i64,
+    // The second token is bought with the first one in the buy pool and sold back in the sell pool
+    pub buy_pool: String,
+    pub sell_pool: String,
+    // Prices of the second token in units of the first one before the trades
+    pub buy_rate: Option<Decimal>,
+    pub sell_rate: Option<Decimal>,
+    // The most profitable amount of the first token and the amounts it's traded for, pool fees
+    // included
+    pub amount_in: u64,
+    pub amount_bought: u64,
+    pub amount_out: u64,
+    // Lovelace kept by the batchers of both orders
+    pub batcher_fee: u64,
+    // Gain in the first token, the batcher fees deducted at the token's price in ADA
+    pub profit: i64,
+    pub block_hash: String,
+    pub slot: u64,
+    pub timestamp: Option<i64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlertKind {
+    // The same wallet trades before and after the victims in the opposite directions
+    Sandwich,
+    // The batcher executes its own order before the orders placed earlier
+    BatcherFrontRunning,
+}
+
+impl AlertKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AlertKind::Sandwich => "sandwich",
+            AlertKind::BatcherFrontRunning => "batcher_front_running",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<AlertKind> {
+        match name {
+            "sandwich" => Some(AlertKind::Sandwich),
+            "batcher_front_running" => Some(AlertKind::BatcherFrontRunning),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct MevAlert {
+    pub event_id: String,
+    pub kind: String,
+    pub script_hash: String,
+    pub asset1: i64,
+    pub asset2: i64,
+    // Hex encoded credential of the profiting wallet, the key hash of the batcher for front
+    // running, null when unknown
+    pub attacker: Option<String>,
+    pub victims: Vec<String>,
+    // Event ids of the swaps of the sequence in the order of their execution
+    pub swaps: Vec<String>,
+    // Transaction of the last swap of the sequence
+    pub tx_hash: String,
+    pub block_hash: String,
+    pub slot: u64,
+    pub timestamp: Option<i64>,
+}
+
+/// Order placed by a transaction waiting in the mempool.
+#[derive(Debug, Serialize)]
+pub struct MempoolOrder {
+    pub script_hash: String,
+    pub output: OutputReference,
+    pub owner: Option<String>,
+    // Sold token and its amount, swap orders only
+    pub offer: Option<AssetAmount>,
+    pub buy: Option<Asset>,
+    pub min_receive: Option<u64>,
+    pub deadline: Option<i64>,
+}
+
+/// Pool update of a transaction waiting in the mempool, the pair in the canonical order.
+#[derive(Debug, Serialize)]
+pub struct MempoolSettlement {
+    pub script_hash: String,
+    pub reserve1: AssetAmount,
+    pub reserve2: AssetAmount,
+    pub swaps: Vec<Swap>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PendingTransaction {
+    pub tx_hash: String,
+    // Slot of the mempool snapshot the transaction was seen in
+    pub slot: u64,
+    pub orders: Vec<MempoolOrder>,
+    pub settlements: Vec<MempoolSettlement>,
 }
 
 #[derive(Debug, Serialize)]
@@ -57,4 +537,11 @@ pub struct SwapInfo {
 pub enum BroadcastMessage {
     MeanValue(ExchangeRate),
     Swap(SwapInfo),
+    AggregatedPrice(AggregatedPrice),
+    OrderBook(OrderBookDepth),
+    Pending(PendingTransaction),
+    // Hash of the pending transaction which left the mempool, included in a block or evicted
+    PendingDropped(String),
+    MevAlert(MevAlert),
+    Arbitrage(ArbitrageOpportunity),
 }
@@ -1,4 +1,16 @@
-use pallas::ledger::addresses::Address;
+use std::collections::HashSet;
+
+use oura::{
+    model::{BlockRecord, TransactionRecord, TxOutputRecord},
+    utils::ChainWellKnownInfo,
+};
+use pallas::ledger::{
+    addresses::{Address, ShelleyDelegationPart, StakePayload},
+    traverse::MultiEraBlock,
+};
+use rust_decimal::Decimal;
+
+use crate::types::{Asset, OutputReference};
 
 // We represent ADA as a token with empty policy_id and name.
 pub static ADA_TOKEN: (Vec<u8>, Vec<u8>) = (Vec::new(), Vec::new());
@@ -11,3 +23,152 @@ pub fn get_payment_hash(address: &str) -> Option<Vec<u8>> {
         None
     }
 }
+
+// Payment and stake credential hashes.
+pub type Credentials = (Option<Vec<u8>>, Option<Vec<u8>>);
+
+/// Payment and stake credential hashes of the address. Stake addresses have only the stake
+/// credential, enterprise and pointer addresses only the payment one.
+pub fn get_credentials(address: &str) -> Option<Credentials> {
+    match Address::from_bech32(address).ok()? {
+        Address::Shelley(address) => Some((
+            Some(address.payment().as_hash().to_vec()),
+            match address.delegation() {
+                ShelleyDelegationPart::Key(hash) | ShelleyDelegationPart::Script(hash) => {
+                    Some(hash.to_vec())
+                }
+                _ => None,
+            },
+        )),
+        Address::Stake(address) => match address.payload() {
+            StakePayload::Stake(hash) | StakePayload::Script(hash) => {
+                Some((None, Some(hash.to_vec())))
+            }
+        },
+        _ => None,
+    }
+}
+
+/// Indices of the block's transactions which failed phase-2 (script) validation.
+///
+/// Oura's transaction records don't carry the validity flag, so it is read from the block CBOR.
+pub fn get_invalid_transactions(block: &BlockRecord) -> anyhow::Result<HashSet<usize>> {
+    let cbor = match &block.cbor_hex {
+        Some(cbor_hex) => hex::decode(cbor_hex)?,
+        None => return Ok(HashSet::new()),
+    };
+    let invalid_transactions = match MultiEraBlock::decode(&cbor)
+        .map_err(|e| anyhow::anyhow!("Cannot decode block {}: {:?}", block.hash, e))?
+    {
+        MultiEraBlock::AlonzoCompatible(b, _) => b.invalid_transactions.clone(),
+        MultiEraBlock::Babbage(b) => b.invalid_transactions.clone(),
+        _ => None,
+    };
+    Ok(invalid_transactions
+        .map(|i| i.to_vec())
+        .unwrap_or_default()
+        .into_iter()
+        .map(|i| i as usize)
+        .collect())
+}
+
+/// Outputs that the transaction really creates on chain, paired with their output index.
+///
+/// A transaction which failed phase-2 validation doesn't create its regular outputs, only the
+/// collateral return output (if any), which is indexed right after the regular outputs.
+pub fn get_produced_outputs(
+    transaction: &TransactionRecord,
+    valid: bool,
+) -> Vec<(usize, &TxOutputRecord)> {
+    if valid {
+        return transaction.outputs.iter().flatten().enumerate().collect();
+    }
+    transaction
+        .collateral_output
+        .iter()
+        .map(|o| (transaction.output_count, o))
+        .collect()
+}
+
+/// Wall-clock time of the slot as a unix timestamp.
+///
+/// Slot length is constant within the Byron and the Shelley-based eras, so the time is computed
+/// linearly from the known reference point of the respective era.
+pub fn slot_to_timestamp(well_known: &ChainWellKnownInfo, slot: u64) -> i64 {
+    let timestamp = if slot < well_known.shelley_known_slot {
+        well_known.byron_known_time
+            + (slot - well_known.byron_known_slot) * well_known.byron_slot_length as u64
+    } else {
+        well_known.shelley_known_time
+            + (slot - well_known.shelley_known_slot) * well_known.shelley_slot_length as u64
+    };
+    timestamp as i64
+}
+
+/// Pairs are stored in the canonical order of the assets, by policy id and then by name. ADA is
+/// therefore always the first token of its pairs.
+pub fn is_canonical_pair(asset1: &Asset, asset2: &Asset) -> bool {
+    (&asset1.policy_id, &asset1.name) <= (&asset2.policy_id, &asset2.name)
+}
+
+/// Exact price of the second token in units of the first one. Returns `None` when the amount of
+/// the second token is zero (or the result doesn't fit into a decimal).
+pub fn get_rate(amount1: impl Into<Decimal>, amount2: impl Into<Decimal>) -> Option<Decimal> {
+    amount1.into().checked_div(amount2.into())
+}
+
+/// Rate of the whole tokens computed from the rate of the smallest units, e.g. ADA instead of
+/// lovelace. Returns `None` when the decimals of either token are unknown.
+pub fn get_adjusted_rate(
+    rate: Option<Decimal>,
+    decimals1: Option<i32>,
+    decimals2: Option<i32>,
+) -> Option<Decimal> {
+    let shift = decimals2? - decimals1?;
+    let factor = match shift >= 0 {
+        true => Decimal::from(10i64.checked_pow(shift as u32)?),
+        false => Decimal::try_from_i128_with_scale(1, shift.unsigned_abs()).ok()?,
+    };
+    rate?.checked_mul(factor)
+}
+
+/// Event id of the price update, a pool has at most one price update per transaction.
+pub fn get_price_event_id(tx_hash: &str, script_hash: &str) -> String {
+    format!("price:{}:{}", tx_hash, script_hash)
+}
+
+/// Event id of the swap, identified by the order it executed. A swap with an unknown order is
+/// identified by its position among the swaps of the pool in the transaction.
+pub fn get_swap_event_id(
+    tx_hash: &str,
+    script_hash: &str,
+    order: Option<&OutputReference>,
+    position: u64,
+) -> String {
+    match order {
+        Some(order) => format!("swap:{}#{}", order.tx_hash, order.index),
+        None => format!("swap:{}:{}:{}", tx_hash, script_hash, position),
+    }
+}
+
+/// Event id of the arbitrage opportunity, two pools of a pair have at most one per block.
+pub fn get_arbitrage_event_id(
+    block_hash: &str,
+    pair: (i64, i64),
+    buy_pool: &str,
+    sell_pool: &str,
+) -> String {
+    format!(
+        "arbitrage:{}:{}:{}:{}:{}",
+        block_hash, pair.0, pair.1, buy_pool, sell_pool
+    )
+}
+
+/// Event id of the MEV alert, identified by the first swap of the suspicious sequence.
+pub fn get_alert_event_id(kind: &str, swaps: &[String]) -> String {
+    format!(
+        "alert:{}:{}",
+        kind,
+        swaps.first().map(|s| s.as_str()).unwrap_or_default()
+    )
+}